@@ -0,0 +1,121 @@
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use gematr::{
+    analyzer::{lexer, parser, syntax_tree::SyntaxTree},
+    evaluation::{
+        number::{Number, Promoting},
+        settings::Settings,
+    },
+    interpreter::{self, batch, bindings::Bindings, bytecode, closure, tracer::NoTracer},
+};
+use num_rational::BigRational;
+use rust_decimal::Decimal;
+
+const EVALUATIONS: u32 = 100_000;
+
+fn parse(expression: &str) -> SyntaxTree {
+    let tokens = lexer::tokenize(expression.chars()).unwrap();
+    parser::parse(tokens).unwrap()
+}
+
+/// Evaluates `evaluate` once per binding of `x` to `0, 1, 2, ...` and returns the time per evaluation.
+fn measure<F: FnMut(&Bindings) -> Decimal>(mut evaluate: F) -> Duration {
+    let mut bindings = Bindings::new();
+    bindings.set("y".to_string(), Decimal::from(3));
+
+    let start = Instant::now();
+
+    for x in 0..EVALUATIONS {
+        bindings.set("x".to_string(), Decimal::from(x));
+        black_box(evaluate(&bindings));
+    }
+
+    start.elapsed() / EVALUATIONS
+}
+
+fn bench(name: &str, expression: &str) {
+    let tree = parse(expression);
+    let program = bytecode::compile(&tree).unwrap();
+    let mut vm = bytecode::Vm::new();
+    let compiled = closure::compile(&tree);
+
+    let interpreting = measure(|bindings| interpreter::interprete_with(tree.clone(), bindings, &mut NoTracer).unwrap());
+    let compiling = measure(|_| bytecode::compile::<Decimal>(&tree).unwrap().code().len().into());
+    let running = measure(|bindings| vm.run(&program, bindings).unwrap());
+    let calling = measure(|bindings| compiled(bindings).unwrap());
+
+    println!(
+        "{name:<24} {:>6} nodes   interprete {:>10.2?}   compile {:>10.2?}   run {:>10.2?}   closure {:>10.2?}",
+        tree.len(),
+        interpreting,
+        compiling,
+        running,
+        calling
+    );
+}
+
+/// Evaluates `expression` for `EVALUATIONS` rows at once, on one thread and on one thread per core.
+fn bench_rows(name: &str, expression: &str) {
+    let tree = parse(expression);
+    let rows: Vec<Bindings> = (0..EVALUATIONS)
+        .map(|x| [("x".to_string(), x.into()), ("y".to_string(), 3.into())].into_iter().collect())
+        .collect();
+
+    let threads = batch::default_threads();
+    let time = |threads| {
+        let start = Instant::now();
+        black_box(batch::evaluate_rows(&tree, &rows, threads, &Settings::default()).unwrap());
+        start.elapsed() / EVALUATIONS
+    };
+
+    println!(
+        "{name:<24} {:>6} rows    1 thread {:>10.2?}   {} threads {:>10.2?}",
+        rows.len(),
+        time(1),
+        threads,
+        time(threads)
+    );
+}
+
+/// Runs `expression` on the VM with `x` bound to `0, 1, 2, ...` and returns the time per evaluation.
+fn time_backend<N: Number>(tree: &SyntaxTree) -> Duration {
+    let program = bytecode::compile::<N>(tree).unwrap();
+    let mut vm = bytecode::Vm::new();
+    let mut bindings = Bindings::new();
+    bindings.set("y", N::parse("3").unwrap());
+
+    let start = Instant::now();
+
+    for x in 0..EVALUATIONS {
+        bindings.set("x", N::parse(&x.to_string()).unwrap());
+        black_box(vm.run(&program, &bindings).unwrap());
+    }
+
+    start.elapsed() / EVALUATIONS
+}
+
+fn bench_backends(name: &str, expression: &str) {
+    let tree = parse(expression);
+
+    println!(
+        "{name:<24} {:>6} nodes   decimal {:>10.2?}   promoting {:>10.2?}   f64 {:>10.2?}   rational {:>10.2?}",
+        tree.len(),
+        time_backend::<Decimal>(&tree),
+        time_backend::<Promoting>(&tree),
+        time_backend::<f64>(&tree),
+        time_backend::<BigRational>(&tree)
+    );
+}
+
+fn main() {
+    bench("linear", "x * 2 + 1");
+    bench("polynomial", "3 * x ^ 3 - 2 * x ^ 2 + x * y - 7");
+    bench("nested calls", "max(min(x, y), min(x * 2, y + 1), -x) / (y + 1)");
+    bench("wide sum", &vec!["x * y + 1"; 100].join(" + "));
+
+    bench_backends("polynomial backends", "3 * x ^ 3 - 2 * x ^ 2 + x * y - 7");
+    bench_rows("polynomial rows", "3 * x ^ 3 - 2 * x ^ 2 + x * y - 7");
+}
@@ -0,0 +1,59 @@
+use std::{
+    hint::black_box,
+    thread,
+    time::{Duration, Instant},
+};
+
+use gematr::analyzer::{dag::Dag, lexer, parser, semantic_analyzer, syntax_tree::SyntaxTree};
+
+const ITERATIONS: u32 = 10;
+
+fn measure<T, F: FnMut() -> T>(mut func: F) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        black_box(func());
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn parse(expression: &str) -> SyntaxTree {
+    let tokens = lexer::tokenize(expression.chars()).unwrap();
+    parser::parse(tokens).unwrap()
+}
+
+fn bench(name: &str, expression: &str) {
+    let tree = parse(expression);
+
+    let parsing = measure(|| parse(expression));
+    let optimization = measure(|| semantic_analyzer::opimize(tree.clone()).unwrap());
+    let sharing = measure(|| Dag::try_from(&tree).unwrap());
+    let cloning = measure(|| tree.clone());
+    let sending = measure(|| {
+        let tree = tree.clone();
+        thread::spawn(move || tree.len()).join().unwrap()
+    });
+
+    println!(
+        "{name:<24} {:>8} nodes   parse {:>10.2?}   optimize {:>10.2?}   share {:>10.2?}   clone {:>10.2?}   send {:>10.2?}",
+        tree.len(),
+        parsing,
+        optimization,
+        sharing,
+        cloning,
+        sending
+    );
+}
+
+fn main() {
+    let depth = 20_000;
+    let width = 100_000;
+
+    bench("nested subtraction", &("1 - (".repeat(depth) + "1" + &")".repeat(depth)));
+    bench("nested negation", &("-(".repeat(depth) + "1" + &")".repeat(depth)));
+    bench("power tower", &vec!["1"; depth].join("^"));
+    bench("wide sum", &vec!["1"; width].join(" + "));
+    bench("wide call", &format!("max({})", vec!["1"; width].join(", ")));
+    bench("wide variable sum", &vec!["x * 2 + 1"; width].join(" + "));
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::{
+    syntax_tree::{NodeId, SyntaxTree},
+    token::{Kind, Span, Token},
+    visitor::Fold,
+};
+
+/// Distinct subexpression of a `Dag`. `value` is the token of its first occurrence in the tree.
+#[derive(Debug, Clone)]
+pub struct DagNode {
+    pub value:    Token,
+    /// Source range of the first occurrence, operands included.
+    pub span:     Span,
+    /// Indices into `Dag::nodes`, always smaller than the index of this node.
+    pub children: Vec<usize>,
+    /// How many times the subexpression occurs in the tree.
+    pub uses:     usize,
+}
+
+/// Syntax tree with structurally identical subtrees merged into one node, so that a subexpression
+/// occurring several times is only evaluated once. Every node kind is pure, evaluating the same
+/// subexpression twice always gives the same value or the same error.
+#[derive(Debug, Clone)]
+pub struct Dag {
+    nodes:    Vec<DagNode>,
+    /// Index of the merged node for every tree node, by `NodeId::index`.
+    merged:   Vec<Option<usize>>,
+    pub root: usize,
+}
+
+impl Dag {
+    /// Nodes in evaluation order: operands left to right before the node using them.
+    pub fn nodes(&self) -> &[DagNode] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Index into `nodes` of the node a tree node was merged into.
+    pub fn index_of(&self, node: NodeId) -> Option<usize> {
+        self.merged.get(node.index()).copied().flatten()
+    }
+
+    /// Number of subexpressions that occur more than once.
+    pub fn shared(&self) -> usize {
+        self.nodes.iter().filter(|node| node.uses > 1).count()
+    }
+}
+
+/// Hash-conses nodes bottom-up: a node is identified by its kind and the identities of its children.
+struct HashConser {
+    nodes:    Vec<DagNode>,
+    merged:   Vec<Option<usize>>,
+    identity: HashMap<(Kind, Vec<usize>), usize>,
+}
+
+impl Fold for HashConser {
+    /// Identity of the node and the source range of this occurrence.
+    type Output = (usize, Span);
+
+    fn fold(&mut self, tree: &SyntaxTree, node: NodeId, children: Vec<(usize, Span)>) -> Result<(usize, Span)> {
+        let value = &tree[node].value;
+        let span = children.iter().fold(tree[node].span(), |span, (_, child)| span.merge(*child));
+        let key = (value.kind.clone(), children.into_iter().map(|(index, _)| index).collect::<Vec<_>>());
+
+        if let Some(index) = self.identity.get(&key) {
+            self.nodes[*index].uses += 1;
+            self.merged[node.index()] = Some(*index);
+            return Ok((*index, span));
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(DagNode {
+            value:    value.clone(),
+            span,
+            children: key.1.clone(),
+            uses:     1,
+        });
+        self.identity.insert(key, index);
+        self.merged[node.index()] = Some(index);
+
+        Ok((index, span))
+    }
+}
+
+impl TryFrom<&SyntaxTree> for Dag {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &SyntaxTree) -> Result<Self, Self::Error> {
+        let mut conser = HashConser {
+            nodes:    vec![],
+            merged:   vec![None; value.capacity()],
+            identity: HashMap::new(),
+        };
+        let (root, _) = value.fold(&mut conser)?;
+
+        Ok(Self {
+            nodes: conser.nodes,
+            merged: conser.merged,
+            root,
+        })
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt::{Display, Formatter};
+
+use super::token::Token;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub line:    usize,
+    pub column:  usize,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, line: usize, column: usize) -> Self {
+        Self {
+            message: message.into(),
+            line,
+            column,
+        }
+    }
+
+    pub fn at(message: impl Into<String>, token: &Token) -> Self {
+        Self::new(message, token.line, token.column)
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on line {} column {}", self.message, self.line, self.column)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Every problem found in a single pass, reported together.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
@@ -0,0 +1,242 @@
+use std::{fmt::Write, ops::ControlFlow, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+
+use super::{
+    syntax_tree::{NodeId, SyntaxTree},
+    token::{self, Span},
+    visitor::{Fold, Visitor},
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TreeFormat {
+    Indent,
+    Dot,
+    Json,
+    Sexpr,
+}
+
+impl FromStr for TreeFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "indent" => Ok(Self::Indent),
+            "dot" => Ok(Self::Dot),
+            "json" => Ok(Self::Json),
+            "sexpr" => Ok(Self::Sexpr),
+            _ => Err(anyhow!("unknown tree format \"{}\", expected indent, dot, json or sexpr", s)),
+        }
+    }
+}
+
+pub fn export(tree: &SyntaxTree, format: TreeFormat) -> Result<String> {
+    match format {
+        TreeFormat::Indent => Ok(tree.to_string()),
+        TreeFormat::Dot => to_dot(tree),
+        TreeFormat::Json => to_json(tree),
+        TreeFormat::Sexpr => to_sexpr(tree),
+    }
+}
+
+/// Name of the node kind used by every exported format.
+pub fn node_kind(kind: &token::Kind) -> &'static str {
+    match kind {
+        token::Kind::Number(_) => "number",
+        token::Kind::Variable(_) => "variable",
+        token::Kind::Func(_) => "call",
+        token::Kind::UnaryOperator(_) => "unary",
+        token::Kind::BinaryOperator(_) => "binary",
+        token::Kind::Error => "error",
+        token::Kind::Parenthesis(_) | token::Kind::Delimeter(_) => "token",
+    }
+}
+
+/// Collects the span of every node, covering the node's own token and all of its descendants.
+struct SpanCollector {
+    spans: Vec<Option<Span>>,
+}
+
+impl Fold for SpanCollector {
+    type Output = Span;
+
+    fn fold(&mut self, tree: &SyntaxTree, node: NodeId, children: Vec<Span>) -> Result<Span> {
+        let span = children.into_iter().fold(tree[node].span(), Span::merge);
+        self.spans[node.index()] = Some(span);
+
+        Ok(span)
+    }
+}
+
+/// Span of every node reachable from the root, indexed by `NodeId::index`.
+pub fn spans(tree: &SyntaxTree) -> Result<Vec<Option<Span>>> {
+    if tree.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut collector = SpanCollector {
+        spans: vec![None; tree.capacity()],
+    };
+    tree.fold(&mut collector)?;
+
+    Ok(collector.spans)
+}
+
+fn format_span(span: Span) -> String {
+    format!("{}:{}-{}:{}", span.line, span.column, span.end_line, span.end_column)
+}
+
+fn written(result: std::fmt::Result) -> ControlFlow<std::fmt::Error> {
+    match result {
+        Ok(()) => ControlFlow::Continue(()),
+        Err(e) => ControlFlow::Break(e),
+    }
+}
+
+struct DotWriter {
+    output: String,
+    spans:  Vec<Option<Span>>,
+}
+
+impl Visitor for DotWriter {
+    type Break = std::fmt::Error;
+
+    fn enter(&mut self, tree: &SyntaxTree, node: NodeId) -> ControlFlow<Self::Break> {
+        let kind = &tree[node].value.kind;
+        let label = format!(
+            "{}\\n{} {}",
+            kind.to_string().replace('\\', "\\\\").replace('"', "\\\""),
+            node_kind(kind),
+            format_span(self.spans[node.index()].unwrap())
+        );
+
+        written(writeln!(self.output, "    n{} [label=\"{}\"];", node.index(), label))?;
+
+        match tree.get_parent(node) {
+            Some(parent) => written(writeln!(self.output, "    n{} -> n{};", parent.index(), node.index())),
+            None => ControlFlow::Continue(()),
+        }
+    }
+}
+
+/// Graphviz digraph with one box per node, labelled with its value, kind and span.
+pub fn to_dot(tree: &SyntaxTree) -> Result<String> {
+    let mut writer = DotWriter {
+        output: String::from("digraph syntax_tree {\n    node [shape=box];\n"),
+        spans:  spans(tree)?,
+    };
+
+    if let ControlFlow::Break(e) = tree.walk(&mut writer) {
+        return Err(e.into());
+    }
+
+    writer.output.push_str("}\n");
+    Ok(writer.output)
+}
+
+struct SexprWriter {
+    output: String,
+    spans:  Vec<Option<Span>>,
+}
+
+impl Visitor for SexprWriter {
+    type Break = std::fmt::Error;
+
+    fn enter(&mut self, tree: &SyntaxTree, node: NodeId) -> ControlFlow<Self::Break> {
+        if tree.get_parent(node).is_some() {
+            self.output.push(' ');
+        }
+
+        let kind = &tree[node].value.kind;
+        written(write!(
+            self.output,
+            "({} {} @{}",
+            node_kind(kind),
+            kind,
+            format_span(self.spans[node.index()].unwrap())
+        ))
+    }
+
+    fn leave(&mut self, _tree: &SyntaxTree, _node: NodeId) -> ControlFlow<Self::Break> {
+        self.output.push(')');
+        ControlFlow::Continue(())
+    }
+}
+
+/// Single line S-expression, every node written as `(kind value @line:column-end_line:end_column children...)`.
+pub fn to_sexpr(tree: &SyntaxTree) -> Result<String> {
+    let mut writer = SexprWriter {
+        output: String::new(),
+        spans:  spans(tree)?,
+    };
+
+    if let ControlFlow::Break(e) = tree.walk(&mut writer) {
+        return Err(e.into());
+    }
+
+    Ok(writer.output)
+}
+
+/// Numbers nodes in pre-order, so the exported ids are dense and independent of the arena layout.
+struct Numbering {
+    ids:   Vec<Option<usize>>,
+    order: Vec<NodeId>,
+}
+
+impl Visitor for Numbering {
+    type Break = ();
+
+    fn enter(&mut self, _tree: &SyntaxTree, node: NodeId) -> ControlFlow<Self::Break> {
+        self.ids[node.index()] = Some(self.order.len());
+        self.order.push(node);
+
+        ControlFlow::Continue(())
+    }
+}
+
+/// Flat JSON document: `root` is the id of the root node and `nodes` lists every node as
+/// `{"id", "kind", "value", "span": {"line", "column", "end_line", "end_column"}, "children": [ids]}`
+/// with ids assigned in pre-order. An empty tree has a `null` root and no nodes.
+pub fn to_json(tree: &SyntaxTree) -> Result<String> {
+    let mut numbering = Numbering {
+        ids:   vec![None; tree.capacity()],
+        order: vec![],
+    };
+    let _ = tree.walk(&mut numbering);
+
+    let spans = spans(tree)?;
+
+    let nodes = numbering
+        .order
+        .iter()
+        .map(|node| {
+            let kind = &tree[*node].value.kind;
+            let span = spans[node.index()].unwrap();
+            let children = tree
+                .get_children(*node)
+                .iter()
+                .map(|child| numbering.ids[child.index()].unwrap())
+                .collect::<Vec<_>>();
+
+            let node = json!({
+                "id": numbering.ids[node.index()].unwrap(),
+                "kind": node_kind(kind),
+                "value": kind.to_string(),
+                "span": {
+                    "line": span.line,
+                    "column": span.column,
+                    "end_line": span.end_line,
+                    "end_column": span.end_column,
+                },
+                "children": children,
+            });
+
+            format!("    {}", node)
+        })
+        .collect::<Vec<_>>();
+
+    // one node per line keeps big trees readable in diffs
+    let root = json!(tree.get_root().map(|_| 0));
+    Ok(format!("{{\n  \"root\": {},\n  \"nodes\": [\n{}\n  ]\n}}\n", root, nodes.join(",\n")))
+}
@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Result};
+
+use super::{
+    syntax_tree::{NodeId, SyntaxTree},
+    token::{self, BinOps, Span, UnOps},
+    visitor::Fold,
+};
+
+/// Typed view of a syntax tree. Unlike `SyntaxNode` every variant carries exactly the operands it needs.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Number {
+        literal: String,
+        span:    Span,
+    },
+    Variable {
+        name: String,
+        span: Span,
+    },
+    Unary {
+        op:      UnOps,
+        operand: Box<Expr>,
+        span:    Span,
+    },
+    Binary {
+        op:   BinOps,
+        lhs:  Box<Expr>,
+        rhs:  Box<Expr>,
+        span: Span,
+    },
+    Call {
+        name: String,
+        args: Vec<Expr>,
+        span: Span,
+    },
+}
+
+impl Expr {
+    /// Covers the operator or literal together with all of its operands.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Number { span, .. }
+            | Self::Variable { span, .. }
+            | Self::Unary { span, .. }
+            | Self::Binary { span, .. }
+            | Self::Call { span, .. } => *span,
+        }
+    }
+
+    fn from_parts(tree: &SyntaxTree, node: NodeId, children: Vec<Expr>) -> Result<Self> {
+        let node = &tree[node];
+
+        let span = children
+            .iter()
+            .fold(node.span(), |span, child| span.merge(child.span()));
+
+        match &node.value.kind {
+            token::Kind::Number(literal) if children.is_empty() => Ok(Self::Number {
+                literal: literal.clone(),
+                span,
+            }),
+
+            token::Kind::Variable(name) if children.is_empty() => Ok(Self::Variable {
+                name: name.clone(),
+                span,
+            }),
+
+            token::Kind::UnaryOperator(op) if children.len() == 1 => {
+                let mut children = children.into_iter();
+
+                Ok(Self::Unary {
+                    op: op.clone(),
+                    operand: Box::new(children.next().unwrap()),
+                    span,
+                })
+            }
+
+            token::Kind::BinaryOperator(op) if children.len() == 2 => {
+                let mut children = children.into_iter();
+
+                Ok(Self::Binary {
+                    op: op.clone(),
+                    lhs: Box::new(children.next().unwrap()),
+                    rhs: Box::new(children.next().unwrap()),
+                    span,
+                })
+            }
+
+            token::Kind::Func(name) => Ok(Self::Call {
+                name: name.clone(),
+                args: children,
+                span,
+            }),
+
+            kind => Err(anyhow!(
+                "malformed `{:?}` node with {} children on line {} column {}",
+                kind,
+                children.len(),
+                node.value.line,
+                node.value.column
+            )),
+        }
+    }
+}
+
+struct ExprBuilder;
+
+impl Fold for ExprBuilder {
+    type Output = Expr;
+
+    fn fold(&mut self, tree: &SyntaxTree, node: NodeId, children: Vec<Expr>) -> Result<Expr> {
+        Expr::from_parts(tree, node, children)
+    }
+}
+
+impl TryFrom<&SyntaxTree> for Expr {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &SyntaxTree) -> Result<Self, Self::Error> {
+        value.fold(&mut ExprBuilder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{lexer, parser, token::Token};
+
+    fn converted(source: &str) -> Expr {
+        let tree = parser::parse(lexer::tokenize(source.chars()).unwrap()).unwrap();
+        Expr::try_from(&tree).unwrap()
+    }
+
+    /// A tree with `root` on top and `children` below it, without going through the parser.
+    fn tree_of(root: Token, children: Vec<Token>) -> SyntaxTree {
+        let mut tree = SyntaxTree::with_root(root);
+        let root = tree.get_root().unwrap();
+
+        for child in children {
+            let child = tree.add_node(child);
+            tree.add_child(root, child);
+        }
+
+        tree
+    }
+
+    fn number(literal: &str, column: usize) -> Token {
+        Token::number(literal.to_string(), 0, column)
+    }
+
+    #[test]
+    fn operands_keep_their_order_and_spans_cover_them() {
+        let expr = converted("max(a, 2 - -b)");
+
+        let Expr::Call { name, args, span } = &expr else {
+            panic!("expected a call, got {:?}", expr)
+        };
+        assert_eq!(name, "max");
+        assert_eq!((span.line, span.column, span.end_line, span.end_column), (0, 1, 0, 14));
+
+        assert!(matches!(&args[0], Expr::Variable { name, .. } if name == "a"));
+
+        let Expr::Binary { op: BinOps::Minus, lhs, rhs, .. } = &args[1] else {
+            panic!("expected `-`, got {:?}", args[1])
+        };
+        assert!(matches!(lhs.as_ref(), Expr::Number { literal, .. } if literal == "2"));
+        assert!(matches!(rhs.as_ref(), Expr::Unary { op: UnOps::Minus, .. }));
+        assert_eq!(rhs.span().column, 12);
+    }
+
+    #[test]
+    fn operators_with_the_wrong_number_of_operands_are_rejected() {
+        let lonely = tree_of(Token::binary_op('+', 0, 3).unwrap(), vec![number("1", 1)]);
+        let crowded = tree_of(Token::unary_op('-', 0, 1).unwrap(), vec![number("1", 2), number("2", 4)]);
+        let parent = tree_of(number("1", 1), vec![number("2", 3)]);
+
+        for tree in [lonely, crowded, parent] {
+            assert!(Expr::try_from(&tree).unwrap_err().to_string().starts_with("malformed"));
+        }
+    }
+
+    #[test]
+    fn parentheses_delimiters_and_error_nodes_are_rejected() {
+        let leaves = [
+            Token::parenthesis('(', 0, 1).unwrap(),
+            Token::delimiter(',', 0, 1).unwrap(),
+            Token::util_delimiter(token::Delim::FuncArgs, 0, 1),
+            Token::error(0, 1),
+        ];
+
+        for leaf in leaves {
+            let tree = tree_of(Token::binary_op('+', 0, 3).unwrap(), vec![number("1", 1), leaf]);
+            assert!(Expr::try_from(&tree).unwrap_err().to_string().starts_with("malformed"));
+        }
+    }
+}
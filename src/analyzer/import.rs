@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use super::{
+    semantic_analyzer,
+    syntax_tree::{NodeId, SyntaxTree},
+    token::Token,
+};
+
+/// Builds a syntax tree from the flat JSON format written by `export::to_json`:
+///
+/// ```json
+/// {
+///   "root": 0,
+///   "nodes": [
+///     {"id": 0, "kind": "binary", "value": "+", "children": [1, 2]},
+///     {"id": 1, "kind": "number", "value": "1"},
+///     {"id": 2, "kind": "call", "value": "max", "children": [3]},
+///     {"id": 3, "kind": "number", "value": "2.5", "span": {"line": 0, "column": 9}}
+///   ]
+/// }
+/// ```
+///
+/// `kind` is one of `number`, `variable`, `unary`, `binary` or `call`, `value` holds the literal, the operator
+/// symbol or the name. `children` lists operands in source order and may be omitted for numbers and variables.
+/// `span` is optional, only its `line` and `column` are used to position errors; `id`s may be any
+/// distinct non-negative integers. The tree must be connected and acyclic, and is checked by
+/// `semantic_analyzer::process` before it is returned.
+pub fn from_json(source: &str) -> Result<SyntaxTree> {
+    let document: Value = serde_json::from_str(source)?;
+
+    let nodes = document
+        .get("nodes")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("expected a \"nodes\" array"))?;
+
+    let mut tree = SyntaxTree::new();
+    let mut ids = HashMap::<u64, NodeId>::new();
+    let mut children = Vec::<(NodeId, Vec<u64>)>::new();
+
+    for (index, node) in nodes.iter().enumerate() {
+        let id = node
+            .get("id")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| anyhow!("node #{} has no valid \"id\"", index))?;
+        let token = token(node).map_err(|e| anyhow!("node {}: {}", id, e))?;
+
+        let node_children = match node.get("children") {
+            None => vec![],
+            Some(list) => list
+                .as_array()
+                .and_then(|list| list.iter().map(Value::as_u64).collect::<Option<Vec<_>>>())
+                .ok_or_else(|| anyhow!("node {}: \"children\" must be an array of ids", id))?,
+        };
+
+        let node_id = tree.add_node(token);
+        if ids.insert(id, node_id).is_some() {
+            return Err(anyhow!("duplicate node id {}", id));
+        }
+        children.push((node_id, node_children));
+    }
+
+    for (parent, node_children) in children {
+        for child in node_children {
+            let child = *ids
+                .get(&child)
+                .ok_or_else(|| anyhow!("child {} does not refer to a node", child))?;
+
+            if tree.get_parent(child).is_some() {
+                return Err(anyhow!("node is listed as a child more than once"));
+            }
+
+            tree.add_child(parent, child);
+        }
+    }
+
+    let root = match document.get("root") {
+        Some(Value::Null) | None => None,
+        Some(root) => {
+            let root = root.as_u64().ok_or_else(|| anyhow!("\"root\" must be a node id or null"))?;
+            Some(*ids.get(&root).ok_or_else(|| anyhow!("root {} does not refer to a node", root))?)
+        }
+    };
+
+    if let Some(root) = root {
+        if tree.get_parent(root).is_some() {
+            return Err(anyhow!("root node is a child of another node"));
+        }
+    }
+    tree.set_root(root);
+
+    // every node has at most one parent, so a node count mismatch means a cycle or a detached node
+    if tree.len() != tree.capacity() {
+        return Err(anyhow!("every node must be reachable from the root exactly once"));
+    }
+
+    semantic_analyzer::process(&tree)?;
+
+    Ok(tree)
+}
+
+fn token(node: &Value) -> Result<Token> {
+    let kind = node.get("kind").and_then(Value::as_str).ok_or_else(|| anyhow!("missing \"kind\""))?;
+    let value = node.get("value").and_then(Value::as_str).ok_or_else(|| anyhow!("missing \"value\""))?;
+
+    let position = |name: &str| {
+        node.get("span")
+            .and_then(|span| span.get(name))
+            .and_then(Value::as_u64)
+            .unwrap_or(0) as usize
+    };
+    let (line, column) = (position("line"), position("column"));
+
+    let symbol = || {
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(anyhow!("\"{}\" is not an operator", value)),
+        }
+    };
+
+    match kind {
+        "number" => Ok(Token::number(value.to_string(), line, column)),
+        "unary" => Token::unary_op(symbol()?, line, column),
+        "binary" => Token::binary_op(symbol()?, line, column),
+        "variable" if is_name(value) => Ok(Token::variable(value.to_string(), line, column)),
+        "variable" => Err(anyhow!("\"{}\" is not a variable name", value)),
+        "call" if is_name(value) => Ok(Token::function(value.to_string(), line, column)),
+        "call" => Err(anyhow!("\"{}\" is not a function name", value)),
+        _ => Err(anyhow!("unknown node kind \"{}\"", kind)),
+    }
+}
+
+/// Names are spelled the way the lexer reads them: a letter followed by letters and digits.
+fn is_name(value: &str) -> bool {
+    value.chars().next().is_some_and(char::is_alphabetic) && value.chars().all(char::is_alphanumeric)
+}
@@ -1,6 +1,9 @@
 use anyhow::{anyhow, Result};
 
-use super::token::{self, Token};
+use super::{
+    diagnostic::{Diagnostic, Diagnostics},
+    token::{self, Token},
+};
 
 pub fn tokenize<T: IntoIterator<Item = char>>(char_stream: T) -> Result<Vec<Token>> {
     let mut tokens = Vec::new();
@@ -13,10 +16,18 @@ pub fn tokenize<T: IntoIterator<Item = char>>(char_stream: T) -> Result<Vec<Toke
     let mut crnt_line = 0;
     let mut crnt_column = 0;
 
+    // every symbol that is not part of the language, reported together once the input is read
+    let mut unknown = Vec::new();
+
     for cc in char_stream {
         crnt_column += 1;
 
-        if cc.is_digit(10) {
+        if cc.is_alphanumeric() && !crnt_func.is_empty() {
+            crnt_func.push(cc);
+            continue;
+        }
+
+        if cc.is_ascii_digit() {
             crnt_number.push(cc);
             continue;
         }
@@ -40,16 +51,11 @@ pub fn tokenize<T: IntoIterator<Item = char>>(char_stream: T) -> Result<Vec<Toke
             continue;
         }
 
-        if cc.is_alphanumeric() && !crnt_func.is_empty() {
-            crnt_func.push(cc);
-            continue;
-        }
-
         if !crnt_number.is_empty() {
             tokens.push(Token::number(
                 crnt_number.clone(),
                 crnt_line,
-                crnt_column - crnt_number.len() + 1,
+                crnt_column - crnt_number.len(),
             ));
 
             crnt_number = String::new();
@@ -60,7 +66,7 @@ pub fn tokenize<T: IntoIterator<Item = char>>(char_stream: T) -> Result<Vec<Toke
             tokens.push(Token::function(
                 crnt_func.clone(),
                 crnt_line,
-                crnt_column - crnt_func.len() + 1,
+                crnt_column - crnt_func.len(),
             ));
 
             crnt_func = String::new();
@@ -69,16 +75,17 @@ pub fn tokenize<T: IntoIterator<Item = char>>(char_stream: T) -> Result<Vec<Toke
         if cc.is_whitespace() {
             if cc == '\n' {
                 crnt_line += 1;
+                crnt_column = 0;
             }
             continue;
         }
 
         if let Ok(t) = Token::parenthesis(cc, crnt_line, crnt_column) {
-            if tokens.last().map_or(false, |t| matches!(t.kind, token::Kind::Func(_)))
+            if tokens.last().is_some_and(|t| matches!(t.kind, token::Kind::Func(_)))
                 && t.kind == token::Kind::Parenthesis(token::Paren::Open)
             {
                 tokens.push(t);
-                tokens.push(Token::util_delimiter(token::Delim::FuncArgs));
+                tokens.push(Token::util_delimiter(token::Delim::FuncArgs, crnt_line, crnt_column));
             } else {
                 tokens.push(t);
             }
@@ -91,14 +98,17 @@ pub fn tokenize<T: IntoIterator<Item = char>>(char_stream: T) -> Result<Vec<Toke
             continue;
         }
 
-        if tokens.last().map_or(true, |t| {
+        if tokens.last().is_none_or(|t| {
             matches!(t.kind, token::Kind::Parenthesis(token::Paren::Open))
                 || matches!(t.kind, token::Kind::BinaryOperator(_))
                 || matches!(t.kind, token::Kind::UnaryOperator(_))
                 || matches!(t.kind, token::Kind::Delimeter(_))
         }) {
-            tokens.push(Token::unary_op(cc, crnt_line, crnt_column)?);
-            continue;
+            // a binary-only operator in operand position is left for the parser to report
+            if let Ok(t) = Token::unary_op(cc, crnt_line, crnt_column) {
+                tokens.push(t);
+                continue;
+            }
         }
 
         if let Ok(t) = Token::binary_op(cc, crnt_line, crnt_column) {
@@ -106,13 +116,13 @@ pub fn tokenize<T: IntoIterator<Item = char>>(char_stream: T) -> Result<Vec<Toke
             continue;
         }
 
-        println!(
-            "warning: symbol '{}' on line {} column {} is ignored",
-            cc, crnt_line, crnt_column
-        );
+        unknown.push(Diagnostic::new(format!("unknown symbol `{}`", cc), crnt_line, crnt_column));
+    }
 
-        crnt_column += 1;
+    if !unknown.is_empty() {
+        return Err(Diagnostics(unknown).into());
     }
+
     if !crnt_number.is_empty() {
         tokens.push(Token::number(
             crnt_number.clone(),
@@ -129,5 +139,35 @@ pub fn tokenize<T: IntoIterator<Item = char>>(char_stream: T) -> Result<Vec<Toke
         ));
     }
 
+    // a name only calls a function when an argument list follows it, otherwise it is a variable
+    for index in 0..tokens.len() {
+        let opens_call = tokens
+            .get(index + 1)
+            .is_some_and(|t| t.kind == token::Kind::Parenthesis(token::Paren::Open));
+
+        if let token::Kind::Func(name) = &tokens[index].kind {
+            if !opens_call {
+                tokens[index] = Token::variable(name.clone(), tokens[index].line, tokens[index].column);
+            }
+        }
+    }
+
     Ok(tokens)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_symbols_are_errors_at_their_position() {
+        let error = tokenize("100!".chars()).unwrap_err();
+        assert_eq!(error.to_string(), "unknown symbol `!` on line 0 column 4");
+
+        let error = tokenize("x / y $\n# 1".chars()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown symbol `$` on line 0 column 7\nunknown symbol `#` on line 1 column 1"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    fmt::{Display, Formatter},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use super::{
+    syntax_tree::SyntaxTree,
+    token::{Kind, Token},
+};
+
+/// Bounds on the work spent on a single expression, for running untrusted input. `None` means unlimited,
+/// which is also the default.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Limits {
+    /// Syntax tree nodes, checked before the tree is built.
+    pub max_nodes: Option<usize>,
+    /// Levels of nesting of the syntax tree, the root is on level 1.
+    pub max_depth: Option<usize>,
+    /// Operators and function calls evaluated by one run of the interpreter, the bytecode VM, a compiled closure
+    /// or an explanation, or by the constant folding of one optimization.
+    pub max_steps: Option<usize>,
+    /// Wall-clock time each of them may take, checked before every step.
+    pub timeout:   Option<Duration>,
+}
+
+/// Error for the limit an expression went over. Each limit has its own variant, so callers can tell them
+/// apart with `anyhow::Error::downcast_ref`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LimitExceeded {
+    Nodes(usize),
+    Depth(usize),
+    Steps(usize),
+    Timeout(Duration),
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nodes(limit) => write!(f, "expression has more than {} nodes", limit),
+            Self::Depth(limit) => write!(f, "expression is nested more than {} levels deep", limit),
+            Self::Steps(limit) => write!(f, "evaluation takes more than {} steps", limit),
+            Self::Timeout(limit) => write!(f, "evaluation takes longer than {:?}", limit),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl Limits {
+    /// Checks the number of nodes the tokens are parsed into, parentheses and commas do not become nodes.
+    pub fn check_tokens(&self, tokens: &[Token]) -> Result<(), LimitExceeded> {
+        let limit = match self.max_nodes {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+
+        let becomes_node = |token: &&Token| {
+            matches!(
+                token.kind,
+                Kind::Number(_) | Kind::Variable(_) | Kind::Func(_) | Kind::UnaryOperator(_) | Kind::BinaryOperator(_)
+            )
+        };
+        let nodes = tokens.iter().filter(becomes_node).count();
+
+        if nodes > limit {
+            return Err(LimitExceeded::Nodes(limit));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the size and depth of a tree that was already built, e.g. one imported from JSON.
+    pub fn check_tree(&self, tree: &SyntaxTree) -> Result<(), LimitExceeded> {
+        if let Some(limit) = self.max_nodes.filter(|limit| tree.len() > *limit) {
+            return Err(LimitExceeded::Nodes(limit));
+        }
+
+        let limit = match self.max_depth {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+
+        // pre-order, so the level of the parent is always known
+        let mut levels = vec![0; tree.capacity()];
+        let mut deepest = 0;
+
+        tree.dfs(|node| {
+            let level = tree.get_parent(node).map_or(1, |parent| levels[parent.index()] + 1);
+            levels[node.index()] = level;
+            deepest = deepest.max(level);
+        });
+
+        if deepest > limit {
+            return Err(LimitExceeded::Depth(limit));
+        }
+
+        Ok(())
+    }
+
+    /// Starts counting the steps and time of one evaluation.
+    pub fn budget(&self) -> Budget {
+        Budget {
+            steps:    0,
+            limits:   *self,
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+}
+
+/// Steps and time left for an evaluation.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    steps:    usize,
+    limits:   Limits,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    /// Accounts for one more step, failing once the steps or the time run out.
+    pub fn step(&mut self) -> Result<(), LimitExceeded> {
+        self.steps += 1;
+
+        if let Some(limit) = self.limits.max_steps.filter(|limit| self.steps > *limit) {
+            return Err(LimitExceeded::Steps(limit));
+        }
+
+        if self.deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            return Err(LimitExceeded::Timeout(self.limits.timeout.unwrap()));
+        }
+
+        Ok(())
+    }
+}
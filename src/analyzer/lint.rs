@@ -0,0 +1,286 @@
+use std::{collections::HashMap, ops::ControlFlow, str::FromStr};
+
+use anyhow::Result;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+use super::{
+    diagnostic::Diagnostic,
+    export,
+    syntax_tree::{NodeId, SyntaxTree},
+    token::{self, BinOps, Token, UnOps},
+    unparser,
+    visitor::Visitor,
+};
+
+use crate::evaluation::number::POWER_BITS;
+
+/// Significant digits a `Decimal` keeps.
+const DECIMAL_DIGITS: usize = 28;
+
+/// Base 10 logarithm of `Decimal::MAX`, larger results overflow.
+const DECIMAL_MAX_LOG10: f64 = 28.898_879_583_742_193;
+
+/// `2 ^ 96` is already past `Decimal::MAX`, so from here on every fractional base of magnitude 2 or more
+/// overflows.
+const OVERFLOWING_EXPONENT: u32 = 96;
+
+/// Value of a literal, possibly behind unary signs.
+fn literal_value(tree: &SyntaxTree, mut node: NodeId) -> Option<Decimal> {
+    let mut negate = false;
+
+    loop {
+        match &tree[node].value.kind {
+            token::Kind::Number(number) => {
+                let value = Decimal::from_str(number).ok()?;
+                return Some(if negate { -value } else { value });
+            }
+            token::Kind::UnaryOperator(op) => {
+                negate ^= *op == UnOps::Minus;
+                node = tree.get_children(node)[0];
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Whether `Decimal` rounds the literal, either because it has too many significant digits
+/// or too many digits after the decimal point. Whole numbers that do not fit become big integers.
+fn loses_precision(literal: &str) -> bool {
+    let literal = literal.trim_start_matches('-');
+    let (whole, fraction) = literal.split_once('.').unwrap_or((literal, ""));
+    let fraction = fraction.trim_end_matches('0');
+
+    let significant = format!("{}{}", whole, fraction).trim_start_matches('0').len();
+
+    !fraction.is_empty() && (significant > DECIMAL_DIGITS || fraction.len() > DECIMAL_DIGITS)
+}
+
+struct TreeLint {
+    warnings: Vec<Diagnostic>,
+}
+
+impl TreeLint {
+    fn power(&mut self, tree: &SyntaxTree, node: NodeId) {
+        let children = tree.get_children(node);
+        let base = literal_value(tree, children[0]);
+        let exponent = match literal_value(tree, children[1]) {
+            Some(exponent) => exponent,
+            None => return,
+        };
+
+        let value = &tree[node].value;
+
+        match base {
+            Some(base) if base.is_zero() && exponent.is_zero() => {
+                self.warnings.push(Diagnostic::at("`0 ^ 0` evaluates to 1", value));
+            }
+
+            Some(base) if !base.is_zero() => {
+                let magnitude = exponent.to_f64().unwrap_or(f64::MAX) * base.abs().to_f64().unwrap_or(1.0).log10();
+                let whole = base.is_integer() && exponent.is_integer() && exponent.is_sign_positive();
+                // powers of 1 and -1 never grow, so they are never refused
+                let grows = base.abs() > Decimal::ONE;
+                // the same estimate the interpreter uses to refuse a power of a big integer
+                let bits = base.abs().to_u128().map_or(0, |base| 128 - base.leading_zeros()) as f64;
+
+                if whole && grows && bits * exponent.to_f64().unwrap_or(f64::MAX) > POWER_BITS as f64 {
+                    let message = format!(
+                        "`{} ^ {}` is about 1e{:.0} and too large even for a big integer",
+                        base, exponent, magnitude
+                    );
+                    self.warnings.push(Diagnostic::at(message, value));
+                } else if !whole && magnitude > DECIMAL_MAX_LOG10 {
+                    let message = format!(
+                        "`{} ^ {}` is about 1e{:.0} and overflows `Decimal`",
+                        base, exponent, magnitude
+                    );
+                    self.warnings.push(Diagnostic::at(message, value));
+                }
+            }
+
+            Some(_) => {}
+
+            None if exponent.abs() >= Decimal::from(OVERFLOWING_EXPONENT) => {
+                let message = format!(
+                    "exponent {} overflows `Decimal` for every fractional base of magnitude 2 or more",
+                    exponent
+                );
+                self.warnings.push(Diagnostic::at(message, value));
+            }
+
+            None => {}
+        }
+    }
+}
+
+impl Visitor for TreeLint {
+    type Break = ();
+
+    fn enter(&mut self, tree: &SyntaxTree, node: NodeId) -> ControlFlow<Self::Break> {
+        let value = &tree[node].value;
+        let divisor_is_zero = || literal_value(tree, tree.get_children(node)[1]).is_some_and(|d| d.is_zero());
+
+        match &value.kind {
+            token::Kind::Number(number) if loses_precision(number) => {
+                let message = format!(
+                    "literal {} has more digits than the {} `Decimal` keeps and is rounded",
+                    number, DECIMAL_DIGITS
+                );
+                self.warnings.push(Diagnostic::at(message, value));
+            }
+
+            token::Kind::BinaryOperator(BinOps::Div) if divisor_is_zero() => {
+                self.warnings.push(Diagnostic::at("division by zero", value));
+            }
+
+            token::Kind::BinaryOperator(BinOps::Mod) if divisor_is_zero() => {
+                self.warnings.push(Diagnostic::at("modulo by zero", value));
+            }
+
+            token::Kind::BinaryOperator(BinOps::Pow) => self.power(tree, node),
+
+            _ => {}
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+type Position = (usize, usize);
+
+/// Parentheses enclosing an operand that would be parsed the same without them. Parentheses are dropped
+/// by the parser, so every pair in `tokens` is matched with the node whose span it encloses.
+fn redundant_parentheses(tokens: &[Token], tree: &SyntaxTree) -> Result<Vec<Diagnostic>> {
+    let is_paren = |token: &Token| {
+        matches!(
+            token.kind,
+            token::Kind::Parenthesis(_) | token::Kind::Delimeter(token::Delim::FuncArgs)
+        )
+    };
+
+    // opening parentheses of every group, innermost first, by the range of what they enclose
+    let mut groups = HashMap::<(Position, Position), Vec<&Token>>::new();
+    // open groups with whether they open a call and the first token inside them that is not a parenthesis
+    let mut open = Vec::<(usize, bool, Option<&Token>)>::new();
+    let mut last: Option<&Token> = None;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.kind {
+            token::Kind::Parenthesis(token::Paren::Open) => {
+                let opens_call = index > 0 && matches!(tokens[index - 1].kind, token::Kind::Func(_));
+                open.push((index, opens_call, None));
+            }
+
+            token::Kind::Parenthesis(token::Paren::Close) => {
+                let (first, start) = match open.pop() {
+                    Some((start, false, first)) => (first, start),
+                    _ => continue,
+                };
+
+                // the last token is inside the group if the group has a first one
+                if let (Some(first), Some(last)) = (first, last) {
+                    let (first, last) = (first.span(), last.span());
+                    let range = ((first.line, first.column), (last.end_line, last.end_column));
+                    groups.entry(range).or_default().push(&tokens[start]);
+                }
+            }
+
+            _ if is_paren(token) => {}
+
+            _ => {
+                // the groups still waiting for their first token are the innermost ones, each gets it once
+                for (_, _, first) in open.iter_mut().rev().take_while(|(_, _, first)| first.is_none()) {
+                    *first = Some(token);
+                }
+                last = Some(token);
+            }
+        }
+    }
+
+    let spans = export::spans(tree)?;
+    let mut nodes = HashMap::<(Position, Position), NodeId>::new();
+
+    // pre-order, so if a parent covers the same range as its operand the parent wins
+    tree.dfs(|node| {
+        let span = spans[node.index()].unwrap();
+        let range = ((span.line, span.column), (span.end_line, span.end_column));
+        nodes.entry(range).or_insert(node);
+    });
+
+    let mut warnings = vec![];
+
+    for (range, parentheses) in groups {
+        let node = match nodes.get(&range) {
+            Some(node) => *node,
+            None => continue,
+        };
+
+        let required = match tree.get_parent(node) {
+            Some(parent) => {
+                let index = tree.find_child(parent, node).unwrap();
+                let needed = unparser::needs_parentheses(&tree[parent].value.kind, index, &tree[node].value.kind);
+
+                usize::from(needed)
+            }
+            None => 0,
+        };
+
+        for paren in parentheses.into_iter().skip(required) {
+            warnings.push(Diagnostic::at("redundant parentheses", paren));
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Warnings about expressions that evaluate, but probably not the way they were meant to. `tokens` are the
+/// ones the tree was parsed from and are only used to find redundant parentheses, pass none for trees
+/// that were not parsed. Warnings are ordered by position.
+pub fn lint(tokens: &[Token], tree: &SyntaxTree) -> Result<Vec<Diagnostic>> {
+    let mut tree_lint = TreeLint { warnings: vec![] };
+    let _ = tree.walk(&mut tree_lint);
+
+    let mut warnings = tree_lint.warnings;
+    warnings.extend(redundant_parentheses(tokens, tree)?);
+    warnings.sort_by_key(|warning| (warning.line, warning.column));
+
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{lexer, parser};
+
+    fn warnings(source: &str) -> Vec<String> {
+        let tokens = lexer::tokenize(source.chars()).unwrap();
+        let tree = parser::parse(tokens.iter().cloned()).unwrap();
+
+        lint(&tokens, &tree)
+            .unwrap()
+            .iter()
+            .map(|w| format!("{}:{} {}", w.line, w.column, w.message))
+            .collect()
+    }
+
+    #[test]
+    fn only_parentheses_the_tree_does_not_need_are_redundant() {
+        assert_eq!(
+            warnings("((1 + 2)) * (3)"),
+            ["0:1 redundant parentheses", "0:13 redundant parentheses"]
+        );
+        assert_eq!(
+            warnings("max((1), (2 + 3))"),
+            ["0:5 redundant parentheses", "0:10 redundant parentheses"]
+        );
+        assert!(warnings("1 - (2 - 3)").is_empty());
+    }
+
+    #[test]
+    fn nested_groups_are_matched_in_one_scan() {
+        let depth = 20_000;
+        let source = format!("{}1{}", "(1 - ".repeat(depth), ")".repeat(depth));
+
+        assert_eq!(warnings(&source), ["0:1 redundant parentheses"]);
+    }
+}
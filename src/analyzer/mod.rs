@@ -1,5 +1,16 @@
+pub mod dag;
+pub mod diagnostic;
+pub mod export;
+pub mod expr;
+pub mod import;
 pub mod lexer;
+pub mod limits;
+pub mod lint;
 pub mod parser;
 pub mod semantic_analyzer;
+pub mod simplifier;
 pub mod syntax_tree;
 pub mod token;
+pub mod types;
+pub mod unparser;
+pub mod visitor;
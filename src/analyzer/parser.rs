@@ -1,64 +1,338 @@
-use std::{collections::VecDeque, rc::Rc};
+use std::{collections::VecDeque, iter::Peekable};
 
 use super::{
-    syntax_tree::{SyntaxNode, SyntaxTree},
+    diagnostic::{Diagnostic, Diagnostics},
+    limits::Limits,
+    syntax_tree::SyntaxTree,
     token::{self, Token},
 };
 use anyhow::{anyhow, Result};
 
+/// Tree built from whatever could be salvaged, together with every syntax error met on the way.
+/// Missing operands are represented by `token::Kind::Error` nodes.
+#[derive(Debug)]
+pub struct ParseOutcome {
+    pub tree:   SyntaxTree,
+    pub errors: Vec<Diagnostic>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Group {
+    Parenthesis,
+    Call,
+}
+
 pub fn parse<T: IntoIterator<Item = Token>>(token_stream: T) -> Result<SyntaxTree> {
-    let mut queue = reverse_polish(token_stream)?;
+    let outcome = parse_recovering(token_stream);
+
+    if outcome.errors.is_empty() {
+        Ok(outcome.tree)
+    } else {
+        Err(Diagnostics(outcome.errors).into())
+    }
+}
+
+/// Like `parse`, refusing input that has more nodes or is nested deeper than `limits` allow. The nodes are
+/// counted before the tree is built.
+pub fn parse_limited(tokens: &[Token], limits: &Limits) -> Result<SyntaxTree> {
+    limits.check_tokens(tokens)?;
+
+    let tree = parse(tokens.iter().cloned())?;
+    limits.check_tree(&tree)?;
+
+    Ok(tree)
+}
+
+pub fn parse_recovering<T: IntoIterator<Item = Token>>(token_stream: T) -> ParseOutcome {
+    let mut errors = Vec::new();
+    let tokens = recover(token_stream, &mut errors);
+
+    let tree = match reverse_polish(tokens) {
+        Ok(queue) => build_tree(queue, &mut errors),
+        Err(e) => {
+            errors.push(Diagnostic::new(e.to_string(), 0, 0));
+            SyntaxTree::new()
+        }
+    };
+
+    ParseOutcome { tree, errors }
+}
+
+/// Checks the token stream for syntax errors and returns a stream the tree builder can always handle.
+/// An expression may span several lines. After an error the rest of the faulty construct is skipped up
+/// to the next `)`, `,` or the end of input, outside of parentheses also up to the next line break. Such
+/// a line break ends the faulty expression and the following lines are checked as an expression of their
+/// own, so that a worksheet gets the errors of all its lines, but only the first expression ends up in
+/// the tree.
+fn recover<T: IntoIterator<Item = Token>>(token_stream: T, errors: &mut Vec<Diagnostic>) -> Vec<Token> {
+    let mut tokens = token_stream.into_iter().peekable();
+    let output = recover_statement(&mut tokens, errors);
+
+    while tokens.peek().is_some() {
+        recover_statement(&mut tokens, errors);
+    }
+
+    output
+}
+
+/// Checks the tokens of one expression, up to the end of input or a line break the parser resynchronized
+/// on after an error.
+fn recover_statement<I: Iterator<Item = Token>>(tokens: &mut Peekable<I>, errors: &mut Vec<Diagnostic>) -> Vec<Token> {
+    let mut output = Vec::<Token>::new();
+    let mut groups = Vec::<(Group, Token)>::new();
+    let mut expect_operand = true;
+    let mut last_position = None;
+
+    while let Some(token) = tokens.next() {
+        last_position = Some((token.line, token.column));
+
+        let starts_operand = matches!(
+            token.kind,
+            token::Kind::Number(_)
+                | token::Kind::Variable(_)
+                | token::Kind::Error
+                | token::Kind::Func(_)
+                | token::Kind::UnaryOperator(_)
+                | token::Kind::Parenthesis(token::Paren::Open)
+        );
+
+        if starts_operand && !expect_operand {
+            errors.push(Diagnostic::at(format!("unexpected `{}` after a complete operand", token.kind), &token));
+
+            let depth = usize::from(token.kind == token::Kind::Parenthesis(token::Paren::Open));
+            let in_call = groups.last().is_some_and(|(group, _)| *group == Group::Call);
+            if synchronize(tokens, depth, in_call, groups.is_empty(), &mut last_position) {
+                break;
+            }
+            continue;
+        }
+
+        match &token.kind {
+            token::Kind::Number(_) | token::Kind::Variable(_) | token::Kind::Error => {
+                output.push(token);
+                expect_operand = false;
+            }
+
+            token::Kind::UnaryOperator(_) => output.push(token),
+
+            token::Kind::Func(name) => {
+                let opens_call = tokens
+                    .peek()
+                    .is_some_and(|t| t.kind == token::Kind::Parenthesis(token::Paren::Open));
+
+                if opens_call {
+                    let paren = tokens.next().unwrap();
+                    let marker = tokens.next_if(|t| t.kind == token::Kind::Delimeter(token::Delim::FuncArgs));
+
+                    groups.push((Group::Call, paren.clone()));
+                    output.push(token);
+                    output.push(paren);
+                    output.extend(marker);
+                } else {
+                    errors.push(Diagnostic::at(format!("function `{}` is missing its argument list", name), &token));
+                    output.push(Token::error(token.line, token.column));
+                    expect_operand = false;
+                }
+            }
+
+            token::Kind::Parenthesis(token::Paren::Open) => {
+                groups.push((Group::Parenthesis, token.clone()));
+                output.push(token);
+            }
+
+            token::Kind::Parenthesis(token::Paren::Close) => {
+                let group = match groups.pop() {
+                    Some((group, _)) => group,
+                    None => {
+                        errors.push(Diagnostic::at("unmatched `)`", &token));
+                        continue;
+                    }
+                };
+
+                let empty_call = group == Group::Call
+                    && output
+                        .last()
+                        .is_some_and(|t| t.kind == token::Kind::Delimeter(token::Delim::FuncArgs));
+
+                if expect_operand && !empty_call {
+                    let otherwise = match group {
+                        Group::Parenthesis => "empty parentheses",
+                        Group::Call => "empty function argument",
+                    };
+                    errors.push(missing_right_operand(&output, otherwise, &token));
+                    output.push(Token::error(token.line, token.column));
+                }
+
+                output.push(token);
+                expect_operand = false;
+            }
+
+            token::Kind::BinaryOperator(op) => {
+                if expect_operand {
+                    errors.push(Diagnostic::at(format!("operator `{}` is missing its left operand", op), &token));
+                    output.push(Token::error(token.line, token.column));
+                }
+
+                output.push(token);
+                expect_operand = true;
+            }
+
+            token::Kind::Delimeter(token::Delim::Comma) => {
+                if !groups.last().is_some_and(|(group, _)| *group == Group::Call) {
+                    errors.push(Diagnostic::at("`,` is only allowed between function arguments", &token));
+
+                    if expect_operand {
+                        output.push(Token::error(token.line, token.column));
+                        expect_operand = false;
+                    }
+
+                    if synchronize(tokens, 0, false, groups.is_empty(), &mut last_position) {
+                        break;
+                    }
+                    continue;
+                }
+
+                if expect_operand {
+                    errors.push(missing_right_operand(&output, "empty function argument", &token));
+                    output.push(Token::error(token.line, token.column));
+                }
+
+                output.push(token);
+                expect_operand = true;
+            }
+
+            token::Kind::Delimeter(token::Delim::FuncArgs) => {}
+        }
+    }
+
+    if expect_operand {
+        match last_position {
+            Some((line, column)) if !output.is_empty() => {
+                let end = Token::error(line, column);
+                errors.push(missing_right_operand(&output, "expected operand at the end of expression", &end));
+                output.push(end);
+            }
+            _ => errors.push(Diagnostic::new("empty expression", 0, 0)),
+        }
+    }
+
+    while let Some((_, paren)) = groups.pop() {
+        errors.push(Diagnostic::at("unclosed `(`", &paren));
+        output.push(Token::parenthesis(')', paren.line, paren.column).unwrap());
+    }
+
+    output
+}
+
+/// Blames the operator an operand is missing after, or reports `otherwise` at `token` if there is none.
+fn missing_right_operand(output: &[Token], otherwise: &str, token: &Token) -> Diagnostic {
+    match output.last() {
+        Some(t @ Token { kind: token::Kind::BinaryOperator(op), .. }) => {
+            Diagnostic::at(format!("operator `{}` is missing its right operand", op), t)
+        }
+        Some(t @ Token { kind: token::Kind::UnaryOperator(op), .. }) => {
+            Diagnostic::at(format!("operator `{}` is missing its operand", op), t)
+        }
+        _ => Diagnostic::at(otherwise, token),
+    }
+}
+
+/// Skips tokens up to, but not including, the next `)` (or `,` when `stop_at_comma` is set)
+/// on the current nesting level. `depth` counts parentheses already opened by skipped tokens.
+/// Outside of parentheses it also stops at a line break and returns `true`, the faulty expression
+/// ends there. `last_position` follows the skipped tokens.
+fn synchronize<I: Iterator<Item = Token>>(
+    tokens: &mut Peekable<I>,
+    mut depth: usize,
+    stop_at_comma: bool,
+    outside_groups: bool,
+    last_position: &mut Option<(usize, usize)>,
+) -> bool {
+    while let Some(token) = tokens.peek() {
+        let new_line = last_position.is_some_and(|(line, _)| token.line > line);
+
+        match token.kind {
+            _ if depth == 0 && outside_groups && new_line => return true,
+            token::Kind::Parenthesis(token::Paren::Open) => depth += 1,
+            token::Kind::Parenthesis(token::Paren::Close) if depth == 0 => return false,
+            token::Kind::Parenthesis(token::Paren::Close) => depth -= 1,
+            token::Kind::Delimeter(token::Delim::Comma) if depth == 0 && stop_at_comma => return false,
+            _ => {}
+        }
 
+        *last_position = Some((token.line, token.column));
+        tokens.next();
+    }
+
+    false
+}
+
+/// Rebuilds the tree from reverse polish notation, checking that every operator gets its operands
+/// and every token ends up in the tree. The queue is consumed from the back, so operands arrive
+/// right to left and each node's children are reversed into source order once it is complete.
+fn build_tree(mut queue: VecDeque<Token>, errors: &mut Vec<Diagnostic>) -> SyntaxTree {
     let token = match queue.pop_back() {
         Some(t) => t,
-        None => return Err(anyhow!("empty expression")),
+        None => return SyntaxTree::new(),
     };
 
-    let syntax_tree = SyntaxTree::with_root(SyntaxNode::new(token).into());
+    let mut syntax_tree = SyntaxTree::with_root(token);
     let mut cursor = syntax_tree.get_root();
 
-    while cursor.is_some() {
-        // Option::clone will trigger Rc::clone
-        let node = Option::clone(&cursor).unwrap();
+    while let Some(node) = cursor {
+        let kind = &syntax_tree[node].value.kind;
+        let children = syntax_tree.get_children(node).len();
 
-        if matches!(node.value.kind, token::Kind::UnaryOperator(_)) && node.get_children().len() == 1 {
-            cursor = node.get_parent_clone();
-            continue;
-        }
+        let complete = match kind {
+            token::Kind::Number(_) | token::Kind::Variable(_) | token::Kind::Error => true,
+            token::Kind::UnaryOperator(_) => children == 1,
+            token::Kind::BinaryOperator(_) => children == 2,
+            _ => false,
+        };
 
-        if matches!(node.value.kind, token::Kind::BinaryOperator(_)) && node.get_children().len() == 2 {
-            cursor = node.get_parent_clone();
+        if complete {
+            syntax_tree[node].children.reverse();
+            cursor = syntax_tree.get_parent(node);
             continue;
         }
 
         let token = match queue.pop_back() {
             Some(t) => t,
-            None => break,
+            None => {
+                let value = &syntax_tree[node].value;
+                errors.push(Diagnostic::at(format!("`{}` is missing an operand", value.kind), value));
+                break;
+            }
         };
 
         match token.kind {
-            token::Kind::Number(_) => {
-                let new_node = Rc::new(SyntaxNode::new(token));
+            token::Kind::Number(_) | token::Kind::Variable(_) | token::Kind::Error => {
+                let new_node = syntax_tree.add_node(token);
 
-                SyntaxTree::add_child(node, new_node);
+                syntax_tree.add_child(node, new_node);
             }
 
             token::Kind::Func(_) | token::Kind::UnaryOperator(_) | token::Kind::BinaryOperator(_) => {
-                let new_node = Rc::new(SyntaxNode::new(token));
-                cursor = Some(Rc::clone(&new_node));
+                let new_node = syntax_tree.add_node(token);
+                cursor = Some(new_node);
 
-                SyntaxTree::add_child(node, new_node);
+                syntax_tree.add_child(node, new_node);
             }
 
             token::Kind::Delimeter(token::Delim::FuncArgs) => {
-                cursor = node.get_parent();
+                syntax_tree[node].children.reverse();
+                cursor = syntax_tree.get_parent(node);
             }
 
             _ => {}
         }
     }
 
-    Ok(syntax_tree)
+    for token in queue.iter().rev() {
+        errors.push(Diagnostic::at(format!("unexpected trailing `{}`", token.kind), token));
+    }
+
+    syntax_tree
 }
 
 pub fn reverse_polish<T: IntoIterator<Item = Token>>(token_stream: T) -> Result<VecDeque<Token>> {
@@ -67,7 +341,7 @@ pub fn reverse_polish<T: IntoIterator<Item = Token>>(token_stream: T) -> Result<
 
     for token in token_stream {
         match token.kind {
-            token::Kind::Number(_) => queue.push_back(token),
+            token::Kind::Number(_) | token::Kind::Variable(_) | token::Kind::Error => queue.push_back(token),
             token::Kind::Func(_) => stack.push_front(token),
             token::Kind::Delimeter(token::Delim::FuncArgs) => queue.push_back(token),
             token::Kind::Parenthesis(token::Paren::Open) => stack.push_front(token),
@@ -103,16 +377,126 @@ pub fn reverse_polish<T: IntoIterator<Item = Token>>(token_stream: T) -> Result<
         }
     }
 
-    while !stack.is_empty() {
-        queue.push_back(stack.pop_front().unwrap());
+    while let Some(token) = stack.pop_front() {
+        queue.push_back(token);
     }
 
-    return Ok(queue);
+    Ok(queue)
 }
 
 fn on_top<F>(stack: &VecDeque<Token>, condition: F) -> bool
 where
     F: Fn(&Token) -> bool,
 {
-    stack.front().map_or(false, condition)
+    stack.front().is_some_and(condition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{lexer, syntax_tree::NodeId};
+
+    /// The tree as an S-expression of token kinds, children in the order they are stored.
+    fn shape(tree: &SyntaxTree, node: NodeId) -> String {
+        let children = tree.get_children(node);
+        let kind = tree[node].value.kind.to_string();
+
+        if children.is_empty() {
+            return kind;
+        }
+
+        let children: Vec<_> = children.iter().map(|child| shape(tree, *child)).collect();
+        format!("({} {})", kind, children.join(" "))
+    }
+
+    fn parsed(source: &str) -> String {
+        let tree = parse(lexer::tokenize(source.chars()).unwrap()).unwrap();
+        shape(&tree, tree.get_root().unwrap())
+    }
+
+    /// Every syntax error of `source` as `line:column message`.
+    fn errors(source: &str) -> Vec<String> {
+        let outcome = parse_recovering(lexer::tokenize(source.chars()).unwrap());
+
+        outcome
+            .errors
+            .iter()
+            .map(|e| format!("{}:{} {}", e.line, e.column, e.message))
+            .collect()
+    }
+
+    #[test]
+    fn binary_operands_are_in_source_order() {
+        assert_eq!(parsed("a - b"), "(- a b)");
+        assert_eq!(parsed("a ^ b"), "(^ a b)");
+        assert_eq!(parsed("a - b - c"), "(- (- a b) c)");
+        assert_eq!(parsed("a ^ b ^ c"), "(^ a (^ b c))");
+    }
+
+    #[test]
+    fn arguments_are_in_source_order() {
+        assert_eq!(parsed("min(a, b, c)"), "(min a b c)");
+        assert_eq!(parsed("max(a - b, min(c, d))"), "(max (- a b) (min c d))");
+    }
+
+    #[test]
+    fn unary_operators_nest_outside_in() {
+        assert_eq!(parsed("-+-a"), "(- (+ (- a)))");
+        assert_eq!(parsed("-a - -b"), "(- (- a) (- b))");
+    }
+
+    #[test]
+    fn expressions_continue_across_lines() {
+        assert_eq!(parsed("1 +\n 2"), "(+ 1 2)");
+        assert_eq!(parsed("1 + 2\n* 4"), "(+ 1 (* 2 4))");
+        assert_eq!(parsed("max(1,\n2)"), "(max 1 2)");
+    }
+
+    #[test]
+    fn a_new_line_does_not_end_a_complete_expression() {
+        assert_eq!(errors("1 + 2\n3 + 4"), ["1:1 unexpected `3` after a complete operand"]);
+    }
+
+    #[test]
+    fn lines_after_an_error_are_checked_on_their_own() {
+        assert_eq!(errors("1 2\n3 + 4"), ["0:3 unexpected `2` after a complete operand"]);
+        assert_eq!(
+            errors("1 2\n3 +\n(4,5)"),
+            [
+                "0:3 unexpected `2` after a complete operand",
+                "2:3 `,` is only allowed between function arguments",
+            ]
+        );
+        assert_eq!(
+            errors("1, 2\n3 4"),
+            [
+                "0:2 `,` is only allowed between function arguments",
+                "1:3 unexpected `4` after a complete operand",
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_input_gets_one_diagnostic_per_fault() {
+        assert_eq!(errors("1 + * 2"), ["0:5 operator `*` is missing its left operand"]);
+        assert_eq!(errors("(1,2)"), ["0:3 `,` is only allowed between function arguments"]);
+        assert_eq!(errors("1 2 3"), ["0:3 unexpected `2` after a complete operand"]);
+        assert_eq!(errors("4 +"), ["0:3 operator `+` is missing its right operand"]);
+        assert_eq!(errors("max(1,,2)"), ["0:7 empty function argument"]);
+        assert_eq!(
+            errors("(1 2) + (3 4)"),
+            [
+                "0:4 unexpected `2` after a complete operand",
+                "0:12 unexpected `4` after a complete operand",
+            ]
+        );
+    }
+
+    #[test]
+    fn dangling_and_unmatched_tokens_are_reported() {
+        assert_eq!(errors(""), ["0:0 empty expression"]);
+        assert_eq!(errors("(1 + 2"), ["0:1 unclosed `(`"]);
+        assert_eq!(errors("1 + 2)"), ["0:6 unmatched `)`"]);
+        assert_eq!(errors("-"), ["0:1 operator `-` is missing its operand"]);
+    }
 }
@@ -1,119 +1,367 @@
-use std::rc::Weak;
-use std::{collections::VecDeque, rc::Rc};
+use std::{
+    fmt::{Display, Formatter},
+    marker::PhantomData,
+    ops::ControlFlow,
+    str::FromStr,
+};
 
-use super::syntax_tree::{SyntaxNode, SyntaxTree};
-use super::token;
+use super::diagnostic::{Diagnostic, Diagnostics};
+use super::limits::Budget;
+use super::simplifier::{Rewrite, Rule, Simplifier};
+use super::syntax_tree::{NodeId, SyntaxTree};
+use super::token::{self, Token};
+use super::visitor::{Rewriter, Visitor};
 use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
 
-pub fn process(syntax_tree: &SyntaxTree) -> Result<()> {
-    if syntax_tree.get_root().is_none() {
-        return Err(anyhow!("empty syntax tree"));
+use crate::evaluation::{
+    engine,
+    number::{Number, Promoting},
+    settings::{RoundAt, Rounding, Settings},
+};
+
+/// Functions the interpreter knows how to evaluate.
+pub const FUNCTIONS: [&str; 2] = ["min", "max"];
+
+/// Checks that every node has a shape the interpreter can evaluate. The parser only ever produces
+/// valid operator arities, the other checks matter for trees built elsewhere, e.g. imported from JSON.
+struct ShapeCheck;
+
+impl Visitor for ShapeCheck {
+    type Break = anyhow::Error;
+
+    fn enter(&mut self, tree: &SyntaxTree, node: NodeId) -> ControlFlow<Self::Break> {
+        let children = tree.get_children(node);
+        let value = &tree[node].value;
+
+        let message = match &value.kind {
+            token::Kind::BinaryOperator(_) if children.len() != 2 => {
+                "wrong number of arguments for binary operator".to_string()
+            }
+
+            token::Kind::UnaryOperator(_) if children.len() != 1 => {
+                "wrong number of arguments for unary operator".to_string()
+            }
+
+            token::Kind::Number(_) if !children.is_empty() => "number can not have operands".to_string(),
+
+            token::Kind::Number(number) if Promoting::parse(number).is_none() => {
+                format!("\"{}\" is not a valid number", number)
+            }
+
+            token::Kind::Variable(_) if !children.is_empty() => "variable can not have operands".to_string(),
+
+            token::Kind::Func(name) if !FUNCTIONS.contains(&name.as_str()) => format!("unknown function `{}`", name),
+
+            token::Kind::Func(name) if children.is_empty() => {
+                format!("function `{}` needs at least one argument", name)
+            }
+
+            token::Kind::Error => "syntax tree contains a syntax error".to_string(),
+
+            token::Kind::Parenthesis(_) | token::Kind::Delimeter(_) => {
+                format!("unexpected `{:?}` in syntax tree", value.kind)
+            }
+
+            _ => return ControlFlow::Continue(()),
+        };
+
+        ControlFlow::Break(Diagnostic::at(message, value).into())
     }
+}
+
+/// Removes unary plus and collapses `--x` into `x`.
+struct UnarySimplifier;
 
-    let mut queue = VecDeque::new();
-    queue.push_front(syntax_tree.get_root().unwrap());
+impl Rewriter for UnarySimplifier {
+    fn rewrite_pre(&mut self, tree: &mut SyntaxTree, node: NodeId) -> Result<Option<NodeId>> {
+        match tree[node].value.kind {
+            token::Kind::UnaryOperator(token::UnOps::Plus) => Ok(Some(tree.get_children(node)[0])),
 
-    while !queue.is_empty() {
-        let node = queue.pop_back().unwrap();
+            token::Kind::UnaryOperator(token::UnOps::Minus) => {
+                let child = tree.get_children(node)[0];
 
-        match node.value.kind {
-            token::Kind::BinaryOperator(_) => {
-                if node.get_children().len() != 2 {
-                    return Err(anyhow!("wrong number of arguments for binary operator"));
+                match &tree[child].value.kind {
+                    token::Kind::UnaryOperator(token::UnOps::Minus) => Ok(Some(tree.get_children(child)[0])),
+                    _ => Ok(None),
                 }
             }
 
-            token::Kind::UnaryOperator(_) => {
-                if node.get_children().len() != 1 {
-                    return Err(anyhow!("wrong number of arguments for unary operator"));
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Replaces every operator and function call whose operands are all literals with the literal it evaluates to
+/// with the numbers of `N`. The literal keeps the span of the subtree it replaces. Evaluation failures such as
+/// division by zero are collected as diagnostics and leave the node unfolded, so does a value whose literal
+/// would not read back as the same value, like NaN. Every fold is a step of `budget`, going over it fails the
+/// whole pass, and its value is rounded as `rounding` rounds every step.
+struct ConstantFolder<'a, N> {
+    errors:   Vec<Diagnostic>,
+    budget:   &'a mut Budget,
+    rounding: Rounding,
+    number:   PhantomData<N>,
+}
+
+impl<N: Number> Rewriter for ConstantFolder<'_, N> {
+    fn rewrite_post(&mut self, tree: &mut SyntaxTree, node: NodeId) -> Result<Option<NodeId>> {
+        let foldable = matches!(
+            tree[node].value.kind,
+            token::Kind::UnaryOperator(_) | token::Kind::BinaryOperator(_) | token::Kind::Func(_)
+        );
+
+        if !foldable {
+            return Ok(None);
+        }
+
+        let children = tree.get_children(node);
+        let mut arguments = Vec::with_capacity(children.len());
+
+        for child in children {
+            match &tree[*child].value.kind {
+                token::Kind::Number(number) => match N::parse(number) {
+                    Some(argument) => arguments.push(argument),
+                    None => return Ok(None),
+                },
+                _ => return Ok(None),
+            }
+        }
+
+        self.budget.step()?;
+        let value = &tree[node].value;
+
+        match engine::apply(&value.kind, &arguments).map(|result| self.rounding.step(result)) {
+            Ok(result) => {
+                let text = result.to_string();
+                if N::parse(&text).is_none_or(|parsed| parsed != result) {
+                    return Ok(None);
                 }
+
+                let span = children.iter().fold(tree[node].span(), |span, child| span.merge(tree[*child].span()));
+
+                let literal = tree.add_node(Token::number(text, span.line, span.column));
+                tree[literal].origin = Some(span);
+
+                Ok(Some(literal))
             }
 
-            _ => {}
+            Err(e) => {
+                self.errors.push(Diagnostic::at(e.to_string(), value));
+                Ok(None)
+            }
         }
+    }
+}
+
+pub fn process(syntax_tree: &SyntaxTree) -> Result<()> {
+    if syntax_tree.get_root().is_none() {
+        return Err(anyhow!("empty syntax tree"));
+    }
 
-        for child in node.get_children().iter() {
-            queue.push_front(Rc::clone(child));
+    match syntax_tree.walk(&mut ShapeCheck) {
+        ControlFlow::Break(e) => Err(e),
+        ControlFlow::Continue(()) => Ok(()),
+    }
+}
+
+/// Which passes `opimize_with` runs. Every level includes the passes of the levels below it.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum OptLevel {
+    /// Only checks the tree.
+    O0,
+    /// Removes unary plus and double negation, then folds constants.
+    O1,
+    /// Also applies the algebraic rules of `simplifier`.
+    O2,
+}
+
+impl FromStr for OptLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(Self::O0),
+            "1" => Ok(Self::O1),
+            "2" => Ok(Self::O2),
+            _ => Err(anyhow!("unknown optimization level \"{}\", expected 0, 1 or 2", s)),
         }
     }
+}
 
-    Ok(())
+/// What a single run of a pass did. Sizes count the nodes reachable from the root.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PassReport {
+    pub pass:      &'static str,
+    pub rewritten: usize,
+    pub before:    usize,
+    pub after:     usize,
+}
+
+impl Display for PassReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<9} rewrote {} nodes, {} -> {} nodes",
+            self.pass, self.rewritten, self.before, self.after
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Optimized {
+    pub tree:     SyntaxTree,
+    /// Every algebraic rule applied, in order.
+    pub rewrites: Vec<Rewrite>,
+    /// Every pass run, in order.
+    pub passes:   Vec<PassReport>,
 }
 
+/// Counts the replacements made by the wrapped pass.
+struct Counted<'a, R> {
+    pass:      &'a mut R,
+    rewritten: usize,
+}
+
+impl<R: Rewriter> Rewriter for Counted<'_, R> {
+    fn rewrite_pre(&mut self, tree: &mut SyntaxTree, node: NodeId) -> Result<Option<NodeId>> {
+        let replacement = self.pass.rewrite_pre(tree, node)?;
+        self.rewritten += usize::from(replacement.is_some());
+
+        Ok(replacement)
+    }
+
+    fn rewrite_post(&mut self, tree: &mut SyntaxTree, node: NodeId) -> Result<Option<NodeId>> {
+        let replacement = self.pass.rewrite_post(tree, node)?;
+        self.rewritten += usize::from(replacement.is_some());
+
+        Ok(replacement)
+    }
+}
+
+fn run_pass<R: Rewriter>(
+    syntax_tree: &mut SyntaxTree,
+    pass: &'static str,
+    rewriter: &mut R,
+    passes: &mut Vec<PassReport>,
+) -> Result<usize> {
+    let before = syntax_tree.len();
+
+    let mut counted = Counted { pass: rewriter, rewritten: 0 };
+    syntax_tree.rewrite(&mut counted)?;
+
+    passes.push(PassReport {
+        pass,
+        rewritten: counted.rewritten,
+        before,
+        after: syntax_tree.len(),
+    });
+
+    Ok(counted.rewritten)
+}
+
+/// Optimizes a tree that is evaluated with `Decimal`, as `interprete` does.
 pub fn opimize(syntax_tree: SyntaxTree) -> Result<SyntaxTree> {
+    Ok(opimize_with::<Decimal>(syntax_tree, OptLevel::O2, &Rule::ALL, &Settings::default())?.tree)
+}
+
+/// Runs the passes of `level`, with only the given algebraic rules enabled, and reports what each of them did.
+/// Constants are computed with `N`, the numbers the tree will be evaluated with, so a folded literal has the
+/// value evaluating its subtree would have given.
+/// Literals the rules expose, as in `min(x - x, 2)`, are folded and simplified again. Folding a constant is a
+/// step of `settings.limits`, so the optimizer gets the same step and time limits as the interpreter. When
+/// `settings.rounding` rounds every step, folded constants are rounded the same way, and the passes that
+/// remove or reorder operations are skipped, since every operation is a point where the value is rounded.
+pub fn opimize_with<N: Number>(
+    syntax_tree: SyntaxTree,
+    level: OptLevel,
+    rules: &[Rule],
+    settings: &Settings,
+) -> Result<Optimized> {
     process(&syntax_tree)?;
 
     let mut syntax_tree = syntax_tree;
+    let mut passes = vec![];
+    let mut simplifier = Simplifier::<N>::new(rules);
+    let mut budget = settings.limits.budget();
+    let rounding = settings.rounding;
+    let rewrite = rounding.at == RoundAt::Output;
 
-    let mut queue = VecDeque::new();
-    queue.push_front(Rc::downgrade(&syntax_tree.get_root().unwrap()));
+    if level >= OptLevel::O1 {
+        if rewrite {
+            run_pass(&mut syntax_tree, "unary", &mut UnarySimplifier, &mut passes)?;
+        }
+        fold_constants::<N>(&mut syntax_tree, &mut passes, &mut budget, rounding)?;
+    }
 
-    while !queue.is_empty() {
-        let node = if let Some(node) = queue.pop_back().unwrap().upgrade() {
-            node
-        } else {
-            continue;
-        };
+    // every rule shrinks the tree, so this ends once a round finds nothing to rewrite
+    if level >= OptLevel::O2 && rewrite {
+        while run_pass(&mut syntax_tree, "simplify", &mut simplifier, &mut passes)? > 0 {
+            fold_constants::<N>(&mut syntax_tree, &mut passes, &mut budget, rounding)?;
+        }
+    }
 
-        match node.value.kind {
-            token::Kind::UnaryOperator(token::UnOps::Plus) => {
-                add_children_to_queue(&mut queue, Rc::clone(&node));
+    Ok(Optimized {
+        tree: syntax_tree,
+        rewrites: simplifier.trace,
+        passes,
+    })
+}
 
-                let child = Rc::clone(node.get_children().get(0).unwrap());
+fn fold_constants<N: Number>(
+    syntax_tree: &mut SyntaxTree,
+    passes: &mut Vec<PassReport>,
+    budget: &mut Budget,
+    rounding: Rounding,
+) -> Result<()> {
+    let mut folder = ConstantFolder::<N> {
+        errors: vec![],
+        budget,
+        rounding,
+        number: PhantomData,
+    };
+    run_pass(syntax_tree, "fold", &mut folder, passes)?;
 
-                if let Some(parent) = node.get_parent() {
-                    SyntaxTree::replace_child(parent, Rc::clone(&node), Rc::clone(&child));
-                } else {
-                    syntax_tree.set_root(Some(Rc::clone(&child)));
-                    child.nullify_parent();
-                }
+    if !folder.errors.is_empty() {
+        return Err(Diagnostics(folder.errors).into());
+    }
 
-                node.remove_child(child);
-            }
+    Ok(())
+}
 
-            token::Kind::UnaryOperator(token::UnOps::Minus) => {
-                let child = Rc::clone(node.get_children().get(0).unwrap());
-
-                if matches!(child.value.kind, token::Kind::UnaryOperator(token::UnOps::Minus)) {
-                    let grandchild = Rc::clone(child.get_children().get(0).unwrap());
-
-                    if let Some(parent) = node.get_parent() {
-                        SyntaxTree::replace_child(Rc::clone(&parent), Rc::clone(&node), Rc::clone(&grandchild));
-                    } else {
-                        syntax_tree.set_root(Some(Rc::clone(&grandchild)));
-                        grandchild.nullify_parent();
-                    }
-
-                    node.remove_child(Rc::clone(&child));
-                    child.remove_child(Rc::clone(&grandchild));
-
-                    queue.push_front(Rc::downgrade(&grandchild));
-                    add_children_to_queue(&mut queue, grandchild)
-                } else if let token::Kind::Number(number) = &child.value.kind {
-                    let mut new_token = child.value.clone();
-                    new_token.kind = token::Kind::Number("-".to_string() + number);
-                    let new_child = Rc::new(SyntaxNode::new(new_token));
-
-                    if let Some(parent) = node.get_parent() {
-                        SyntaxTree::replace_child(parent, Rc::clone(&node), new_child);
-
-                        node.remove_child(Rc::clone(&child));
-                        child.nullify_parent();
-                    } else {
-                        syntax_tree.set_root(Some(Rc::clone(&new_child)));
-                        new_child.nullify_parent();
-                    }
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analyzer::{lexer, parser, unparser},
+        evaluation::number::Promoting,
+    };
+    use num_rational::BigRational;
 
-            _ => add_children_to_queue(&mut queue, node),
-        }
+    fn optimized<N: Number>(source: &str) -> Result<String> {
+        let tree = parser::parse(lexer::tokenize(source.chars())?)?;
+        let optimized = opimize_with::<N>(tree, OptLevel::O2, &Rule::ALL, &Settings::default())?;
+
+        unparser::unparse(&optimized.tree)
     }
-    Ok(syntax_tree)
-}
 
-fn add_children_to_queue(queue: &mut VecDeque<Weak<SyntaxNode>>, node: Rc<SyntaxNode>) {
-    for next_node in node.get_children().iter() {
-        queue.push_front(Rc::downgrade(next_node));
+    #[test]
+    fn constants_are_folded_with_the_evaluating_backend() {
+        assert_eq!(optimized::<Promoting>("10^30 + 1").unwrap(), "1000000000000000000000000000001");
+        assert!(optimized::<Decimal>("10^30 + 1").unwrap_err().to_string().contains("out of range"));
+        assert_eq!(optimized::<BigRational>("1/3 + x + 1/3").unwrap(), "x + 2/3");
+        assert_eq!(optimized::<f64>("0.1 + 0.2").unwrap(), "0.30000000000000004");
+    }
+
+    #[test]
+    fn values_without_a_literal_are_left_unfolded() {
+        assert_eq!(optimized::<f64>("0 / 0 + 1").unwrap(), "0 / 0 + 1");
+    }
+
+    #[test]
+    fn opimize_folds_with_decimal() {
+        let tree = parser::parse(lexer::tokenize("2^100 - 2^100".chars()).unwrap()).unwrap();
+
+        assert!(opimize(tree).is_err());
     }
 }
@@ -0,0 +1,336 @@
+use std::{
+    fmt::{Display, Formatter},
+    marker::PhantomData,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use super::{
+    semantic_analyzer::FUNCTIONS,
+    syntax_tree::{NodeId, SyntaxTree},
+    token::{self, BinOps, Span, Token},
+    unparser,
+    visitor::Rewriter,
+};
+use crate::evaluation::{engine, number::Number};
+
+/// Algebraic identity the `Simplifier` can apply. Unlike constant folding the rules also apply
+/// around operands that are not literals, such as variables and function calls.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Rule {
+    /// `x * 1` and `1 * x` become `x`.
+    MulOne,
+    /// `x + 0`, `0 + x` and `x - 0` become `x`.
+    AddZero,
+    /// `x ^ 1` becomes `x`.
+    PowOne,
+    /// `x * 0` and `0 * x` become `0` if evaluating `x` can not fail, so only without variables.
+    MulZero,
+    /// `x - x` becomes `0` if evaluating `x` can not fail, so only without variables.
+    SubSelf,
+    /// Gathers the literals of a `+` or `*` chain into one, `1 + x + 2` becomes `x + 3`.
+    Reassociate,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Self::MulOne,
+        Self::AddZero,
+        Self::PowOne,
+        Self::MulZero,
+        Self::SubSelf,
+        Self::Reassociate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MulOne => "mul-one",
+            Self::AddZero => "add-zero",
+            Self::PowOne => "pow-one",
+            Self::MulZero => "mul-zero",
+            Self::SubSelf => "sub-self",
+            Self::Reassociate => "reassociate",
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|rule| rule.name() == s).ok_or_else(|| {
+            let names = Self::ALL.map(Self::name).join(", ");
+            anyhow!("unknown rule \"{}\", expected one of {}", s, names)
+        })
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// One application of a rule: the source range it covered and the subtree before and after.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rewrite {
+    pub rule:   Rule,
+    pub span:   Span,
+    pub before: String,
+    pub after:  String,
+}
+
+impl Display for Rewrite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} => {} on line {} column {}",
+            self.rule, self.before, self.after, self.span.line, self.span.column
+        )
+    }
+}
+
+/// What a rule turns the visited node into.
+enum Action {
+    /// One of the node's operands takes its place.
+    Keep(NodeId),
+    /// A literal takes its place.
+    Literal(&'static str),
+    /// The non-literal terms of a chain, joined by the chain's operator and followed by the
+    /// combined literal unless it is the operator's identity.
+    Regroup {
+        terms:    Vec<NodeId>,
+        literals: Vec<NodeId>,
+        constant: String,
+    },
+}
+
+/// Applies the enabled rules bottom-up, so a rewrite can enable another one on the enclosing node.
+/// Literals are combined with the numbers of `N`. Every application is recorded in `trace`.
+pub struct Simplifier<N> {
+    rules:     Vec<Rule>,
+    pub trace: Vec<Rewrite>,
+    number:    PhantomData<N>,
+}
+
+impl<N: Number> Simplifier<N> {
+    pub fn new(rules: &[Rule]) -> Self {
+        Self {
+            rules: rules.to_vec(),
+            trace: vec![],
+            number: PhantomData,
+        }
+    }
+
+    fn enabled(&self, rule: Rule) -> bool {
+        self.rules.contains(&rule)
+    }
+
+    fn find(&self, tree: &SyntaxTree, node: NodeId) -> Option<(Rule, Action)> {
+        let op = match &tree[node].value.kind {
+            token::Kind::BinaryOperator(op) => op,
+            _ => return None,
+        };
+
+        let children = tree.get_children(node);
+        let (lhs, rhs) = (children[0], children[1]);
+        // `1.0` is not an identity, `x * 1.0` has one more digit after the point than `x`
+        let is = |node: NodeId, value: Decimal| match &tree[node].value.kind {
+            token::Kind::Number(number) => Decimal::from_str(number).is_ok_and(|l| l == value && l.scale() == 0),
+            _ => false,
+        };
+
+        match op {
+            BinOps::Mult if self.enabled(Rule::MulOne) && is(rhs, Decimal::ONE) => {
+                return Some((Rule::MulOne, Action::Keep(lhs)))
+            }
+            BinOps::Mult if self.enabled(Rule::MulOne) && is(lhs, Decimal::ONE) => {
+                return Some((Rule::MulOne, Action::Keep(rhs)))
+            }
+            BinOps::Plus | BinOps::Minus if self.enabled(Rule::AddZero) && is(rhs, Decimal::ZERO) => {
+                return Some((Rule::AddZero, Action::Keep(lhs)))
+            }
+            BinOps::Plus if self.enabled(Rule::AddZero) && is(lhs, Decimal::ZERO) => {
+                return Some((Rule::AddZero, Action::Keep(rhs)))
+            }
+            BinOps::Pow if self.enabled(Rule::PowOne) && is(rhs, Decimal::ONE) => {
+                return Some((Rule::PowOne, Action::Keep(lhs)))
+            }
+            BinOps::Mult
+                if self.enabled(Rule::MulZero)
+                    && (is(rhs, Decimal::ZERO) && infallible(tree, lhs)
+                        || is(lhs, Decimal::ZERO) && infallible(tree, rhs)) =>
+            {
+                return Some((Rule::MulZero, Action::Literal("0")))
+            }
+            BinOps::Minus
+                if self.enabled(Rule::SubSelf) && tree.same_subtree(lhs, rhs) && infallible(tree, lhs) =>
+            {
+                return Some((Rule::SubSelf, Action::Literal("0")))
+            }
+            _ => {}
+        }
+
+        if self.enabled(Rule::Reassociate) {
+            return reassociate::<N>(tree, node, op).map(|action| (Rule::Reassociate, action));
+        }
+
+        None
+    }
+
+    fn apply(&self, tree: &mut SyntaxTree, node: NodeId, action: Action) -> NodeId {
+        match action {
+            Action::Keep(operand) => operand,
+
+            Action::Literal(value) => {
+                let span = tree.subtree_span(node);
+                new_literal(tree, value.to_string(), span)
+            }
+
+            Action::Regroup {
+                terms,
+                literals,
+                constant,
+            } => {
+                let operator = tree[node].value.clone();
+                let mut terms = terms.into_iter();
+                let mut result = terms.next().unwrap();
+
+                let identity = match operator.kind {
+                    token::Kind::BinaryOperator(BinOps::Mult) => "1",
+                    _ => "0",
+                };
+
+                let literal = (constant != identity).then(|| {
+                    let span = literals
+                        .iter()
+                        .map(|literal| tree[*literal].span())
+                        .reduce(Span::merge)
+                        .unwrap();
+                    new_literal(tree, constant, span)
+                });
+
+                for term in terms.chain(literal) {
+                    let joined = tree.add_node(operator.clone());
+                    tree.add_child(joined, result);
+                    tree.add_child(joined, term);
+                    result = joined;
+                }
+
+                result
+            }
+        }
+    }
+}
+
+impl<N: Number> Rewriter for Simplifier<N> {
+    fn rewrite_post(&mut self, tree: &mut SyntaxTree, node: NodeId) -> Result<Option<NodeId>> {
+        let (rule, action) = match self.find(tree, node) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let span = tree.subtree_span(node);
+        let before = unparser::unparse_subtree(tree, node)?;
+        let new_node = self.apply(tree, node, action);
+
+        self.trace.push(Rewrite {
+            rule,
+            span,
+            before,
+            after: unparser::unparse_subtree(tree, new_node)?,
+        });
+
+        Ok(Some(new_node))
+    }
+}
+
+fn literal<N: Number>(tree: &SyntaxTree, node: NodeId) -> Option<N> {
+    match &tree[node].value.kind {
+        token::Kind::Number(number) => N::parse(number),
+        _ => None,
+    }
+}
+
+fn new_literal(tree: &mut SyntaxTree, value: String, span: Span) -> NodeId {
+    let literal = tree.add_node(Token::number(value, span.line, span.column));
+    tree[literal].origin = Some(span);
+
+    literal
+}
+
+/// Whether evaluating the subtree always succeeds, which is the case for subtrees built only from
+/// literals, negation and the known functions. A variable fails when it has no value, and the
+/// optimizer does not know the values.
+fn infallible(tree: &SyntaxTree, node: NodeId) -> bool {
+    let mut stack = vec![node];
+
+    while let Some(node) = stack.pop() {
+        let safe = match &tree[node].value.kind {
+            token::Kind::Number(_) | token::Kind::UnaryOperator(_) => true,
+            token::Kind::Func(name) => FUNCTIONS.contains(&name.as_str()),
+            _ => false,
+        };
+
+        if !safe {
+            return false;
+        }
+
+        stack.extend(tree.get_children(node));
+    }
+
+    true
+}
+
+/// Combines the literals of the `+` or `*` chain topped by `node`. Only the top of a chain is
+/// rewritten, and only if it holds at least two literals.
+fn reassociate<N: Number>(tree: &SyntaxTree, node: NodeId, op: &BinOps) -> Option<Action> {
+    if !matches!(op, BinOps::Plus | BinOps::Mult) {
+        return None;
+    }
+
+    let kind = token::Kind::BinaryOperator(op.clone());
+
+    if tree.get_parent(node).is_some_and(|parent| tree[parent].value.kind == kind) {
+        return None;
+    }
+
+    let mut terms = vec![];
+    let mut literals = vec![];
+    let mut values = vec![];
+    let mut stack = vec![node];
+
+    while let Some(node) = stack.pop() {
+        if tree[node].value.kind == kind {
+            stack.extend(tree.get_children(node).iter().rev());
+        } else if let Some(value) = literal::<N>(tree, node) {
+            literals.push(node);
+            values.push(value);
+        } else {
+            terms.push(node);
+        }
+    }
+
+    if literals.len() < 2 || terms.is_empty() {
+        return None;
+    }
+
+    let mut constant = values[0].clone();
+    for value in &values[1..] {
+        // leave the chain alone if combining its literals overflows
+        constant = engine::apply(&kind, &[constant, value.clone()]).ok()?;
+    }
+
+    let text = constant.to_string();
+    if N::parse(&text).is_none_or(|parsed| parsed != constant) {
+        return None;
+    }
+
+    Some(Action::Regroup {
+        terms,
+        literals,
+        constant: text,
+    })
+}
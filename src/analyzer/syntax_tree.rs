@@ -1,152 +1,244 @@
 use std::{
-    cell::{Ref, RefCell, RefMut},
+    collections::VecDeque,
     fmt::{Display, Formatter},
-    rc::{Rc, Weak}, collections::VecDeque,
+    ops::{ControlFlow, Index, IndexMut},
 };
 
-use super::token::Token;
+use super::{
+    token::{Span, Token},
+    visitor::Visitor,
+};
+
+/// Handle of a node inside the `SyntaxTree` that allocated it.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
 
-#[derive(Debug)]
+/// Nodes live in a single arena owned by the tree and refer to each other by `NodeId`,
+/// so the whole tree is plain data: cheap to clone and safe to send across threads.
+/// Nodes detached by rewrites stay in the arena until the tree is compacted.
+#[derive(Debug, Clone, Default)]
 pub struct SyntaxTree {
-    pub root: Option<Rc<SyntaxNode>>,
+    nodes:    Vec<SyntaxNode>,
+    pub root: Option<NodeId>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SyntaxNode {
     pub value:    Token,
-    pub children: RefCell<Vec<Rc<SyntaxNode>>>,
-    pub parent:   RefCell<Weak<SyntaxNode>>,
+    pub children: Vec<NodeId>,
+    pub parent:   Option<NodeId>,
+    /// Source range of the subtree a rewrite replaced with this node.
+    pub origin:   Option<Span>,
 }
 
 impl SyntaxNode {
     pub fn new(value: Token) -> Self {
         Self {
-            value:    value,
-            children: RefCell::new(vec![]),
-            parent:   RefCell::new(Weak::new()),
+            value,
+            children: vec![],
+            parent: None,
+            origin: None,
         }
     }
 
-    fn print_recursive(&self, ind: usize, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let indent = " ".repeat(ind);
-        write!(f, "{indent}{:?}\n", self.value.kind)?;
-        for child in self.children.borrow().iter() {
-            child.print_recursive(ind + 6, f)?;
+    /// Source range of the node's own token, or of everything it was rewritten from.
+    pub fn span(&self) -> Span {
+        self.origin.unwrap_or_else(|| self.value.span())
+    }
+}
+
+impl SyntaxTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            root:  None,
         }
+    }
+
+    pub fn with_root(value: Token) -> Self {
+        let mut tree = Self::new();
+        let root = tree.add_node(value);
+        tree.set_root(Some(root));
 
-        Ok(())
+        tree
     }
 
-    pub fn get_parent(&self) -> Option<Rc<SyntaxNode>> {
-        self.parent.borrow().upgrade()
+    /// Allocates a detached node, attach it with `add_child` or `set_root`.
+    pub fn add_node(&mut self, value: Token) -> NodeId {
+        self.nodes.push(SyntaxNode::new(value));
+        NodeId(self.nodes.len() - 1)
     }
 
-    pub fn get_parent_clone(&self) -> Option<Rc<SyntaxNode>> {
-        self.get_parent().map(|p| Rc::clone(&p))
+    pub fn set_root(&mut self, node: Option<NodeId>) {
+        if let Some(node) = node {
+            self[node].parent = None;
+        }
+
+        self.root = node;
     }
 
-    pub fn set_parent(&self, parent: Rc<SyntaxNode>) {
-        *self.parent.borrow_mut() = Rc::downgrade(&parent);
+    pub fn get_root(&self) -> Option<NodeId> {
+        self.root
     }
 
-    pub fn nullify_parent(&self) {
-        *self.parent.borrow_mut() = Weak::new();
+    pub fn get_parent(&self, node: NodeId) -> Option<NodeId> {
+        self[node].parent
     }
 
-    pub fn get_children(&self) -> Ref<Vec<Rc<SyntaxNode>>> {
-        self.children.borrow()
+    pub fn get_children(&self, node: NodeId) -> &[NodeId] {
+        &self[node].children
     }
 
-    pub fn get_children_mut(&self) -> RefMut<Vec<Rc<SyntaxNode>>> {
-        self.children.borrow_mut()
+    /// Number of nodes reachable from the root.
+    pub fn len(&self) -> usize {
+        let mut count = 0;
+        self.dfs(|_| count += 1);
+        count
     }
 
-    pub fn add_child(&self, child: Rc<SyntaxNode>) {
-        self.get_children_mut().push(child);
+    /// Number of allocated nodes, detached ones included. Every `NodeId` of the tree is below it.
+    pub fn capacity(&self) -> usize {
+        self.nodes.len()
     }
 
-    pub fn find_child(&self, child: Rc<SyntaxNode>) -> Option<usize> {
-        let child_ptr = Rc::as_ptr(&child);
-        let mut index = None;
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
 
-        for (i, c) in self.get_children().iter().enumerate() {
-            let c_ptr = Rc::as_ptr(c);
+    pub fn add_child(&mut self, parent: NodeId, child: NodeId) {
+        self[parent].children.push(child);
+        self[child].parent = Some(parent);
+    }
 
-            if child_ptr == c_ptr {
-                index = Some(i);
-                break;
-            }
-        }
+    pub fn prepend_child(&mut self, parent: NodeId, child: NodeId) {
+        self[parent].children.insert(0, child);
+        self[child].parent = Some(parent);
+    }
 
-        return index;
+    pub fn find_child(&self, parent: NodeId, child: NodeId) -> Option<usize> {
+        self[parent].children.iter().position(|c| *c == child)
     }
 
-    pub fn remove_child(&self, child: Rc<SyntaxNode>) {
-        if let Some(index) = self.find_child(child) {
-            self.get_children_mut().remove(index);
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId) {
+        if let Some(index) = self.find_child(parent, child) {
+            self[parent].children.remove(index);
+            self[child].parent = None;
         }
     }
-}
 
-impl SyntaxTree {
-    #[allow(dead_code)]
-    pub fn new() -> Self {
-        Self { root: None }
+    pub fn replace_child(&mut self, parent: NodeId, original_child: NodeId, new_child: NodeId) {
+        if let Some(index) = self.find_child(parent, original_child) {
+            self[parent].children[index] = new_child;
+            self[original_child].parent = None;
+            self[new_child].parent = Some(parent);
+        }
     }
 
-    pub fn with_root(root_node: Option<Rc<SyntaxNode>>) -> Self {
-        Self { root: root_node }
+    /// Puts `new_node` wherever `original` is attached, be it under a parent or as the root.
+    pub fn replace_node(&mut self, original: NodeId, new_node: NodeId) {
+        match self.get_parent(original) {
+            Some(parent) => self.replace_child(parent, original, new_node),
+            None => {
+                self.set_root(Some(new_node));
+                self[original].parent = None;
+            }
+        }
     }
 
-    pub fn set_root(&mut self, node: Option<Rc<SyntaxNode>>) {
-        self.root = node;
+    /// Source range of `node` together with all of its descendants.
+    pub fn subtree_span(&self, node: NodeId) -> Span {
+        let mut span = self[node].span();
+        let mut stack = self.get_children(node).to_vec();
+
+        while let Some(node) = stack.pop() {
+            span = span.merge(self[node].span());
+            stack.extend(self.get_children(node));
+        }
+
+        span
     }
 
-    pub fn get_root(&self) -> Option<Rc<SyntaxNode>> {
-        if let Some(ref r) = self.root {
-            Some(Rc::clone(&r))
-        } else {
-            None
+    /// Whether the subtrees under `a` and `b` spell the same expression, ignoring positions.
+    pub fn same_subtree(&self, a: NodeId, b: NodeId) -> bool {
+        let mut stack = vec![(a, b)];
+
+        while let Some((a, b)) = stack.pop() {
+            let (a_children, b_children) = (self.get_children(a), self.get_children(b));
+
+            if self[a].value.kind != self[b].value.kind || a_children.len() != b_children.len() {
+                return false;
+            }
+
+            stack.extend(a_children.iter().copied().zip(b_children.iter().copied()));
         }
+
+        true
     }
 
-    pub fn add_child(parent: Rc<SyntaxNode>, child: Rc<SyntaxNode>) {
-        parent.add_child(child.clone());
-        child.set_parent(parent);
+    /// Returns a copy without the nodes detached by earlier rewrites.
+    pub fn compact(&self) -> Self {
+        let mut tree = Self::new();
+
+        if let Some(root) = self.root {
+            let new_root = self.copy_subtree(root, &mut tree);
+            tree.set_root(Some(new_root));
+        }
+
+        tree
     }
 
-    pub fn replace_child(parent: Rc<SyntaxNode>, original_child: Rc<SyntaxNode>, new_child: Rc<SyntaxNode>) {
-        if let Some(index) = parent.find_child(Rc::clone(&original_child)) {
-            parent.get_children_mut()[index] = Rc::clone(&new_child);
-            original_child.nullify_parent();
-            new_child.set_parent(parent);
+    /// Copies the subtree under `node` into `target` and returns the id of the copy.
+    pub fn copy_subtree(&self, node: NodeId, target: &mut SyntaxTree) -> NodeId {
+        let copy = target.add_node(self[node].value.clone());
+        target[copy].origin = self[node].origin;
+        let mut stack = vec![(node, copy)];
+
+        while let Some((original, copy)) = stack.pop() {
+            for child in self.get_children(original) {
+                let child_copy = target.add_node(self[*child].value.clone());
+                target[child_copy].origin = self[*child].origin;
+                target.add_child(copy, child_copy);
+                stack.push((*child, child_copy));
+            }
         }
+
+        copy
     }
 
-    pub fn dfs<F>(&self, func: F) where F: Fn(Rc<SyntaxNode>) {
-        let mut stack = VecDeque::<Rc<SyntaxNode>>::new();
+    pub fn dfs<F>(&self, mut func: F)
+    where
+        F: FnMut(NodeId),
+    {
+        let mut stack = VecDeque::<NodeId>::new();
 
         let root = if let Some(r) = self.get_root() {
             r
-        }  else {
+        } else {
             return;
         };
 
         stack.push_back(root);
 
-        while !stack.is_empty() {
-            let node = stack.pop_back().unwrap();
+        while let Some(node) = stack.pop_back() {
+            func(node);
 
-            func(Rc::clone(&node));
-
-            for child in node.get_children().iter() {
-                stack.push_back(Rc::clone(child));
+            for child in self.get_children(node) {
+                stack.push_back(*child);
             }
         }
     }
 
-    pub fn bfs<F>(&self, func: F) where F: Fn(Rc<SyntaxNode>) {
-        let mut queue = VecDeque::<Rc<SyntaxNode>>::new();
+    pub fn bfs<F>(&self, mut func: F)
+    where
+        F: FnMut(NodeId),
+    {
+        let mut queue = VecDeque::<NodeId>::new();
 
         let root = if let Some(r) = self.get_root() {
             r
@@ -156,36 +248,64 @@ impl SyntaxTree {
 
         queue.push_front(root);
 
-        while !queue.is_empty() {
-            let node = queue.pop_back().unwrap();
-
-            func(Rc::clone(&node));
+        while let Some(node) = queue.pop_back() {
+            func(node);
 
-            for child in node.get_children().iter() {
-                queue.push_front(Rc::clone(child));
+            for child in self.get_children(node) {
+                queue.push_front(*child);
             }
         }
     }
 }
 
-impl Display for SyntaxNode {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        self.print_recursive(2, f)
+impl Index<NodeId> for SyntaxTree {
+    type Output = SyntaxNode;
+
+    fn index(&self, index: NodeId) -> &Self::Output {
+        &self.nodes[index.0]
     }
 }
 
-impl Display for SyntaxTree {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if let Some(node) = &self.root {
-            writeln!(f, "{}", node)
-        } else {
-            writeln!(f, "{{}}")
+impl IndexMut<NodeId> for SyntaxTree {
+    fn index_mut(&mut self, index: NodeId) -> &mut Self::Output {
+        &mut self.nodes[index.0]
+    }
+}
+
+struct IndentPrinter<'a, 'b> {
+    f:      &'a mut Formatter<'b>,
+    indent: usize,
+}
+
+impl Visitor for IndentPrinter<'_, '_> {
+    type Break = std::fmt::Error;
+
+    fn enter(&mut self, tree: &SyntaxTree, node: NodeId) -> ControlFlow<Self::Break> {
+        let indent = " ".repeat(self.indent);
+        self.indent += 6;
+
+        match writeln!(self.f, "{indent}{:?}", tree[node].value.kind) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
         }
     }
+
+    fn leave(&mut self, _tree: &SyntaxTree, _node: NodeId) -> ControlFlow<Self::Break> {
+        self.indent -= 6;
+        ControlFlow::Continue(())
+    }
 }
 
-impl From<SyntaxNode> for Option<Rc<SyntaxNode>> {
-    fn from(value: SyntaxNode) -> Self {
-        return Some(value.into());
+impl Display for SyntaxTree {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.root.is_none() {
+            return writeln!(f, "{{}}");
+        }
+
+        if let ControlFlow::Break(e) = self.walk(&mut IndentPrinter { f, indent: 2 }) {
+            return Err(e);
+        }
+
+        writeln!(f)
     }
 }
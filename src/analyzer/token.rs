@@ -1,3 +1,5 @@
+use std::fmt::{Display, Formatter};
+
 use anyhow::{anyhow, Result};
 
 #[repr(usize)]
@@ -36,7 +38,7 @@ impl Associativity {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum BinOps {
     Plus,
     Minus,
@@ -62,7 +64,7 @@ impl TryFrom<char> for BinOps {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum UnOps {
     Plus,
     Minus,
@@ -80,7 +82,7 @@ impl TryFrom<char> for UnOps {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Paren {
     Close,
     Open,
@@ -98,7 +100,7 @@ impl TryFrom<char> for Paren {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Delim {
     FuncArgs,
     Comma,
@@ -115,14 +117,41 @@ impl TryFrom<char> for Delim {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Kind {
     Number(String),
+    /// Name that is not followed by an argument list.
+    Variable(String),
     Func(String),
     Parenthesis(Paren),
     BinaryOperator(BinOps),
     UnaryOperator(UnOps),
     Delimeter(Delim),
+    /// Placeholder the parser puts where an operand is missing.
+    Error,
+}
+
+/// Source range covered by a token or an expression, `end` points just past the last character.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub line:       usize,
+    pub column:     usize,
+    pub end_line:   usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    pub fn merge(self, other: Span) -> Span {
+        let (line, column) = (self.line, self.column).min((other.line, other.column));
+        let (end_line, end_column) = (self.end_line, self.end_column).max((other.end_line, other.end_column));
+
+        Span {
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -140,8 +169,18 @@ impl Token {
             assoc:  Associativity::Left,
             prec:   Precedence::Default,
             kind:   Kind::Number(number),
-            line:   line,
-            column: column,
+            line,
+            column,
+        }
+    }
+
+    pub fn variable(name: String, line: usize, column: usize) -> Self {
+        Self {
+            assoc:  Associativity::Left,
+            prec:   Precedence::Default,
+            kind:   Kind::Variable(name),
+            line,
+            column,
         }
     }
 
@@ -150,8 +189,8 @@ impl Token {
             assoc:  Associativity::Left,
             prec:   Precedence::Function,
             kind:   Kind::Func(func),
-            line:   line,
-            column: column,
+            line,
+            column,
         }
     }
 
@@ -182,8 +221,8 @@ impl Token {
             assoc:  Associativity::for_binary_op(&op),
             prec:   Precedence::for_binary_op(&op),
             kind:   Kind::BinaryOperator(op),
-            line:   line,
-            column: column,
+            line,
+            column,
         })
     }
 
@@ -192,18 +231,78 @@ impl Token {
             assoc:  Associativity::Left,
             prec:   Precedence::Default,
             kind:   Kind::Delimeter(Delim::try_from(delim)?),
-            line:   line,
-            column: column,
+            line,
+            column,
         })
     }
 
-    pub fn util_delimiter(delim: Delim) -> Self {
+    pub fn util_delimiter(delim: Delim, line: usize, column: usize) -> Self {
         Self {
-            assoc:  Associativity::Left,
-            prec:   Precedence::Default,
-            kind:   Kind::Delimeter(delim),
-            line:   0,
-            column: 0,
+            assoc: Associativity::Left,
+            prec: Precedence::Default,
+            kind: Kind::Delimeter(delim),
+            line,
+            column,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        Span {
+            line:       self.line,
+            column:     self.column,
+            end_line:   self.line,
+            end_column: self.column + self.kind.to_string().chars().count(),
+        }
+    }
+
+    pub fn error(line: usize, column: usize) -> Self {
+        Self {
+            assoc: Associativity::Left,
+            prec: Precedence::Default,
+            kind: Kind::Error,
+            line,
+            column,
+        }
+    }
+}
+
+impl Display for BinOps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Self::Plus => '+',
+            Self::Minus => '-',
+            Self::Mult => '*',
+            Self::Div => '/',
+            Self::Mod => '%',
+            Self::Pow => '^',
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Display for UnOps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plus => write!(f, "+"),
+            Self::Minus => write!(f, "-"),
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{}", number),
+            Self::Variable(name) => write!(f, "{}", name),
+            Self::Func(name) => write!(f, "{}", name),
+            Self::Parenthesis(Paren::Open) => write!(f, "("),
+            Self::Parenthesis(Paren::Close) => write!(f, ")"),
+            Self::BinaryOperator(op) => write!(f, "{}", op),
+            Self::UnaryOperator(op) => write!(f, "{}", op),
+            Self::Delimeter(Delim::Comma) => write!(f, ","),
+            Self::Delimeter(Delim::FuncArgs) => Ok(()),
+            Self::Error => write!(f, "<error>"),
         }
     }
 }
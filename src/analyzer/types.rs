@@ -0,0 +1,129 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+
+use super::{
+    diagnostic::Diagnostic,
+    syntax_tree::{NodeId, SyntaxTree},
+    token::{self, BinOps},
+    visitor::Fold,
+};
+
+/// Kind of value a node evaluates to. The language only has decimal numbers, so an expression either
+/// provably yields a whole number or may yield any number; there are no booleans, lists, bitwise operators
+/// or units that could be mixed up, and every operator accepts both types.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Type {
+    Integer,
+    Number,
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer => write!(f, "integer"),
+            Self::Number => write!(f, "number"),
+        }
+    }
+}
+
+/// Type of every node reachable from the root.
+#[derive(Debug, Clone)]
+pub struct Types(Vec<Option<Type>>);
+
+impl Types {
+    pub fn get(&self, node: NodeId) -> Option<Type> {
+        self.0.get(node.index()).copied().flatten()
+    }
+}
+
+struct TypeInference {
+    types: Vec<Option<Type>>,
+}
+
+impl Fold for TypeInference {
+    type Output = Type;
+
+    fn fold(&mut self, tree: &SyntaxTree, node: NodeId, children: Vec<Type>) -> Result<Type> {
+        let value = &tree[node].value;
+        let all_integers = children.iter().all(|child| *child == Type::Integer);
+
+        let inferred = match &value.kind {
+            kind if whole_literal(kind).is_some() => Type::Integer,
+            token::Kind::Number(_) | token::Kind::Variable(_) => Type::Number,
+
+            token::Kind::UnaryOperator(_) | token::Kind::Func(_) => children.into_iter().max().unwrap_or(Type::Number),
+
+            token::Kind::BinaryOperator(BinOps::Div) => Type::Number,
+
+            // only a whole, non-negative exponent keeps a whole base whole
+            token::Kind::BinaryOperator(BinOps::Pow) => {
+                let exponent = tree.get_children(node)[1];
+
+                if all_integers && whole_literal(&tree[exponent].value.kind).is_some_and(|e| !e.is_sign_negative()) {
+                    Type::Integer
+                } else {
+                    Type::Number
+                }
+            }
+
+            token::Kind::BinaryOperator(_) if all_integers => Type::Integer,
+            token::Kind::BinaryOperator(_) => Type::Number,
+
+            kind => return Err(Diagnostic::at(format!("can not infer the type of `{}`", kind), value).into()),
+        };
+
+        self.types[node.index()] = Some(inferred);
+        Ok(inferred)
+    }
+}
+
+/// Value of a literal written without a fractional part, like `12` or `-12` but unlike `12.0`.
+fn whole_literal(kind: &token::Kind) -> Option<Decimal> {
+    match kind {
+        token::Kind::Number(number) => Decimal::from_str(number).ok().filter(|value| value.scale() == 0),
+        _ => None,
+    }
+}
+
+/// Infers the type of every node bottom-up. Variables are treated as any number until they are bound.
+pub fn infer(tree: &SyntaxTree) -> Result<Types> {
+    let mut inference = TypeInference {
+        types: vec![None; tree.capacity()],
+    };
+
+    if !tree.is_empty() {
+        tree.fold(&mut inference)?;
+    }
+
+    Ok(Types(inference.types))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{lexer, parser};
+
+    fn root_type(source: &str) -> Type {
+        let tree = parser::parse(lexer::tokenize(source.chars()).unwrap()).unwrap();
+        infer(&tree).unwrap().get(tree.get_root().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn whole_operands_give_whole_results() {
+        assert_eq!(root_type("7 % 2"), Type::Integer);
+        assert_eq!(root_type("-(2 ^ 3) * max(1, 2)"), Type::Integer);
+    }
+
+    #[test]
+    fn any_operator_accepts_any_number() {
+        assert_eq!(root_type("7 % 2.5"), Type::Number);
+        assert_eq!(root_type("7 % (5 / 2)"), Type::Number);
+        assert_eq!(root_type("2 ^ -1"), Type::Number);
+        assert_eq!(root_type("x + 1"), Type::Number);
+    }
+}
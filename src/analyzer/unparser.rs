@@ -0,0 +1,174 @@
+use std::fmt::{Display, Write};
+
+use anyhow::{anyhow, Result};
+
+use super::{
+    syntax_tree::{NodeId, SyntaxTree},
+    token::{self, Associativity, Precedence},
+};
+
+/// How tightly a node binds. `None` stands for atoms like numbers and calls, which never need parentheses.
+fn precedence(kind: &token::Kind) -> Option<Precedence> {
+    match kind {
+        token::Kind::Number(number) if number.starts_with('-') => Some(Precedence::OperatorUnary),
+        token::Kind::UnaryOperator(_) => Some(Precedence::OperatorUnary),
+        token::Kind::BinaryOperator(op) => Some(Precedence::for_binary_op(op)),
+        _ => None,
+    }
+}
+
+/// Whether the operand at `index` of a `parent` node has to be parenthesized to stay its operand.
+/// An operand of equal precedence only binds without parentheses on the associative side.
+pub fn needs_parentheses(parent: &token::Kind, index: usize, operand: &token::Kind) -> bool {
+    let prec = match precedence(operand) {
+        Some(prec) => prec,
+        None => return false,
+    };
+
+    match parent {
+        token::Kind::UnaryOperator(_) => prec < Precedence::OperatorUnary,
+
+        token::Kind::BinaryOperator(op) => {
+            let parent_prec = Precedence::for_binary_op(op);
+            let loose_side = match index {
+                0 => Associativity::Right,
+                _ => Associativity::Left,
+            };
+
+            prec < parent_prec || prec == parent_prec && Associativity::for_binary_op(op) == loose_side
+        }
+
+        _ => false,
+    }
+}
+
+/// What is still to be written. A node stands for its whole subtree.
+enum Piece<'t> {
+    Node(NodeId),
+    Text(&'t dyn Display),
+}
+
+/// Writes the source text of the subtree under `root` into one buffer, with the `highlighted` nodes wrapped
+/// in `[` and `]`. The pieces of a node are pushed in reverse, so they come off the stack in source order.
+fn write_source(tree: &SyntaxTree, root: NodeId, highlighted: &[NodeId]) -> Result<String> {
+    let mut text = String::new();
+    let mut stack = vec![Piece::Node(root)];
+
+    while let Some(piece) = stack.pop() {
+        let node = match piece {
+            Piece::Node(node) => node,
+            Piece::Text(piece) => {
+                write!(text, "{}", piece)?;
+                continue;
+            }
+        };
+
+        let kind = &tree[node].value.kind;
+        let children = tree.get_children(node);
+
+        let operand = |stack: &mut Vec<Piece>, index: usize| {
+            let child = children[index];
+
+            if needs_parentheses(kind, index, &tree[child].value.kind) {
+                stack.extend([Piece::Text(&")"), Piece::Node(child), Piece::Text(&"(")]);
+            } else {
+                stack.push(Piece::Node(child));
+            }
+        };
+
+        if highlighted.contains(&node) {
+            stack.push(Piece::Text(&"]"));
+        }
+
+        match kind {
+            token::Kind::UnaryOperator(op) => {
+                operand(&mut stack, 0);
+                stack.push(Piece::Text(op));
+            }
+
+            token::Kind::BinaryOperator(op) => {
+                operand(&mut stack, 1);
+                stack.extend([Piece::Text(&" "), Piece::Text(op), Piece::Text(&" ")]);
+                operand(&mut stack, 0);
+            }
+
+            token::Kind::Func(name) => {
+                stack.push(Piece::Text(&")"));
+                for index in (0..children.len()).rev() {
+                    operand(&mut stack, index);
+                    if index > 0 {
+                        stack.push(Piece::Text(&", "));
+                    }
+                }
+                stack.extend([Piece::Text(&"("), Piece::Text(name)]);
+            }
+
+            kind => stack.push(Piece::Text(kind)),
+        }
+
+        if highlighted.contains(&node) {
+            stack.push(Piece::Text(&"["));
+        }
+    }
+
+    Ok(text)
+}
+
+/// Turns a tree back into infix source with only the parentheses its shape requires,
+/// so that parsing the result yields the same tree.
+pub fn unparse(tree: &SyntaxTree) -> Result<String> {
+    unparse_highlighted(tree, &[])
+}
+
+/// Source text of the subtree under `node` alone.
+pub fn unparse_subtree(tree: &SyntaxTree, node: NodeId) -> Result<String> {
+    write_source(tree, node, &[])
+}
+
+/// Like `unparse`, marking the subexpressions under `nodes` as `[subexpression]`.
+pub fn unparse_highlighted(tree: &SyntaxTree, nodes: &[NodeId]) -> Result<String> {
+    let root = tree.get_root().ok_or_else(|| anyhow!("empty syntax tree"))?;
+
+    write_source(tree, root, nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{lexer, parser};
+
+    fn tree(source: &str) -> SyntaxTree {
+        parser::parse(lexer::tokenize(source.chars()).unwrap()).unwrap()
+    }
+
+    fn unparsed(source: &str) -> String {
+        unparse(&tree(source)).unwrap()
+    }
+
+    #[test]
+    fn only_required_parentheses_are_kept() {
+        assert_eq!(unparsed("((1 + 2)) * 3"), "(1 + 2) * 3");
+        assert_eq!(unparsed("(a - b) - (c - d)"), "a - b - (c - d)");
+        assert_eq!(unparsed("(2 ^ 3) ^ 4 + 2 ^ (3 ^ 4)"), "(2 ^ 3) ^ 4 + 2 ^ 3 ^ 4");
+        assert_eq!(unparsed("-(a + b) * max(a, (-b), min(1))"), "-(a + b) * max(a, -b, min(1))");
+    }
+
+    #[test]
+    fn highlighted_subexpressions_are_bracketed() {
+        let tree = tree("1 + 2 * 3");
+        let root = tree.get_root().unwrap();
+        let product = tree.get_children(root)[1];
+
+        assert_eq!(unparse_highlighted(&tree, &[product]).unwrap(), "1 + [2 * 3]");
+        assert_eq!(unparse_highlighted(&tree, &[root, product]).unwrap(), "[1 + [2 * 3]]");
+        assert_eq!(unparse_subtree(&tree, product).unwrap(), "2 * 3");
+    }
+
+    #[test]
+    fn deep_nesting_is_written_in_one_pass() {
+        let depth = 20_000;
+        let source = format!("{}1{}", "(1 - ".repeat(depth), ")".repeat(depth));
+
+        assert_eq!(unparsed(&source), source.replacen("(", "", 1).replacen(")", "", 1));
+    }
+}
@@ -0,0 +1,120 @@
+use std::ops::ControlFlow;
+
+use anyhow::{anyhow, Result};
+
+use super::syntax_tree::{NodeId, SyntaxTree};
+
+/// Read-only pass over a tree. `enter` runs before a node's children (pre-order) and `leave` after them
+/// (post-order), children are visited left to right. Returning `ControlFlow::Break` stops the walk.
+pub trait Visitor {
+    type Break;
+
+    fn enter(&mut self, _tree: &SyntaxTree, _node: NodeId) -> ControlFlow<Self::Break> {
+        ControlFlow::Continue(())
+    }
+
+    fn leave(&mut self, _tree: &SyntaxTree, _node: NodeId) -> ControlFlow<Self::Break> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// Computes a value for every node from the values of its children, bottom-up.
+pub trait Fold {
+    type Output;
+
+    fn fold(&mut self, tree: &SyntaxTree, node: NodeId, children: Vec<Self::Output>) -> Result<Self::Output>;
+}
+
+/// Pass that edits the tree in place. A hook returning `Some(new_node)` makes the tree put `new_node`
+/// where the visited node was attached.
+pub trait Rewriter {
+    /// Called before the children are rewritten. A replacement is offered to `rewrite_pre` again.
+    fn rewrite_pre(&mut self, _tree: &mut SyntaxTree, _node: NodeId) -> Result<Option<NodeId>> {
+        Ok(None)
+    }
+
+    /// Called once the children are rewritten.
+    fn rewrite_post(&mut self, _tree: &mut SyntaxTree, _node: NodeId) -> Result<Option<NodeId>> {
+        Ok(None)
+    }
+}
+
+enum Step {
+    Enter(NodeId),
+    Leave(NodeId),
+}
+
+impl SyntaxTree {
+    pub fn walk<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        let mut stack: Vec<Step> = self.get_root().map(Step::Enter).into_iter().collect();
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(node) => {
+                    visitor.enter(self, node)?;
+
+                    stack.push(Step::Leave(node));
+                    stack.extend(self.get_children(node).iter().rev().map(|child| Step::Enter(*child)));
+                }
+
+                Step::Leave(node) => visitor.leave(self, node)?,
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    pub fn fold<F: Fold>(&self, folder: &mut F) -> Result<F::Output> {
+        let root = self.get_root().ok_or_else(|| anyhow!("empty syntax tree"))?;
+
+        self.fold_subtree(root, folder)
+    }
+
+    /// Folds only the subtree under `node`.
+    pub fn fold_subtree<F: Fold>(&self, node: NodeId, folder: &mut F) -> Result<F::Output> {
+        let mut stack = vec![Step::Enter(node)];
+        let mut values = Vec::<F::Output>::new();
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(node) => {
+                    stack.push(Step::Leave(node));
+                    stack.extend(self.get_children(node).iter().rev().map(|child| Step::Enter(*child)));
+                }
+
+                Step::Leave(node) => {
+                    let children = values.split_off(values.len() - self.get_children(node).len());
+                    values.push(folder.fold(self, node, children)?);
+                }
+            }
+        }
+
+        Ok(values.pop().unwrap())
+    }
+
+    pub fn rewrite<R: Rewriter>(&mut self, rewriter: &mut R) -> Result<()> {
+        let mut stack: Vec<Step> = self.get_root().map(Step::Enter).into_iter().collect();
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(mut node) => {
+                    while let Some(new_node) = rewriter.rewrite_pre(self, node)? {
+                        self.replace_node(node, new_node);
+                        node = new_node;
+                    }
+
+                    stack.push(Step::Leave(node));
+                    stack.extend(self.get_children(node).iter().rev().map(|child| Step::Enter(*child)));
+                }
+
+                Step::Leave(node) => {
+                    if let Some(new_node) = rewriter.rewrite_post(self, node)? {
+                        self.replace_node(node, new_node);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
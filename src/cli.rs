@@ -0,0 +1,168 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Result};
+use gematr::{
+    analyzer::{export::TreeFormat, semantic_analyzer::OptLevel, simplifier::Rule},
+    evaluation::{
+        number::Backend,
+        settings::{Precision, RoundAt, Settings},
+    },
+    interpreter::{batch, tracer::TraceFormat},
+};
+
+const USAGE: &str = "usage: gematr [--input-format expr|json] [--tree-format indent|dot|json|sexpr] \
+                     [-O0|-O1|-O2] [--disable-rule RULE]... [--trace none|text|json] [--explain] \
+                     [--number decimal|f64|rational] [--set NAME=VALUE]... [--csv ROWS [--threads N]] \
+                     [--max-nodes N] [--max-depth N] [--max-steps N] [--timeout MS] \
+                     [--places N|--digits N] [--rounding MODE] [--round-every-step] [FILE]";
+
+/// What the input file contains: an expression or a syntax tree in the JSON format of `import::from_json`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InputFormat {
+    Expr,
+    Json,
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "expr" => Ok(Self::Expr),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("unknown input format \"{}\", expected expr or json", s)),
+        }
+    }
+}
+
+/// Value of an option that takes a count, which has to be at least 1.
+fn positive(name: &str, value: &str) -> Result<usize> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(anyhow!("{} expects a positive whole number, got \"{}\"", name, value)),
+    }
+}
+
+/// Value of an option that takes a number of digits, which may be 0.
+fn whole(name: &str, value: &str) -> Result<u32> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("{} expects a whole number, got \"{}\"", name, value))
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub path:         String,
+    pub input_format: InputFormat,
+    pub tree_format:  TreeFormat,
+    pub opt_level:    OptLevel,
+    /// Algebraic rules the optimizer may apply.
+    pub rules:        Vec<Rule>,
+    /// How evaluation steps are printed.
+    pub trace:        TraceFormat,
+    /// Rewrite the whole expression after every step instead of tracing the steps.
+    pub explain:      bool,
+    /// `name=value` assignments of the variables in the expression, read by the number backend.
+    pub assignments:  Vec<String>,
+    /// Numbers the expression is evaluated with.
+    pub number:       Backend,
+    /// CSV file to evaluate the expression for every row of, `-` for standard input.
+    pub csv:          Option<String>,
+    /// Threads evaluating the rows of `csv`.
+    pub threads:      usize,
+    /// Limits and rounding of the evaluation.
+    pub settings:     Settings,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            path:         String::from("examples/expr12.gm"),
+            input_format: InputFormat::Expr,
+            tree_format:  TreeFormat::Indent,
+            opt_level:    OptLevel::O2,
+            rules:        Rule::ALL.to_vec(),
+            trace:        TraceFormat::Text,
+            explain:      false,
+            assignments:  vec![],
+            number:       Backend::Decimal,
+            csv:          None,
+            threads:      batch::default_threads(),
+            settings:     Settings::default(),
+        }
+    }
+}
+
+impl Options {
+    /// Parses the arguments following the program name. Options take their value either
+    /// as `--option value` or as `--option=value`.
+    pub fn parse<T: IntoIterator<Item = String>>(args: T) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        let mut path = None;
+        let mut opt_level = None;
+
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow!("missing value for {}\n{}", name, USAGE))
+            };
+
+            match name.as_str() {
+                "--input-format" => options.input_format = value()?.parse()?,
+                "--tree-format" => options.tree_format = value()?.parse()?,
+                _ if name.starts_with("-O") => opt_level = Some(name[2..].parse()?),
+                "--trace" => options.trace = value()?.parse()?,
+                "--explain" => options.explain = true,
+                "--set" => options.assignments.push(value()?),
+                "--number" => options.number = value()?.parse()?,
+                "--csv" => options.csv = Some(value()?),
+                "--threads" => options.threads = positive(&name, &value()?)?,
+                "--max-nodes" => options.settings.limits.max_nodes = Some(positive(&name, &value()?)?),
+                "--max-depth" => options.settings.limits.max_depth = Some(positive(&name, &value()?)?),
+                "--max-steps" => options.settings.limits.max_steps = Some(positive(&name, &value()?)?),
+                "--places" => options.settings.rounding.precision = Precision::Places(whole(&name, &value()?)?),
+                "--digits" => {
+                    let digits = positive(&name, &value()?)?;
+                    options.settings.rounding.precision = Precision::Significant(digits as u32);
+                }
+                "--rounding" => options.settings.rounding.mode = value()?.parse()?,
+                "--round-every-step" => options.settings.rounding.at = RoundAt::EveryStep,
+                "--timeout" => {
+                    let milliseconds = positive(&name, &value()?)?;
+                    options.settings.limits.timeout = Some(Duration::from_millis(milliseconds as u64));
+                }
+                "--disable-rule" => {
+                    let rule: Rule = value()?.parse()?;
+                    options.rules.retain(|r| *r != rule);
+                }
+                "-h" | "--help" => return Err(anyhow!(USAGE)),
+                _ if name.starts_with('-') => return Err(anyhow!("unknown option {}\n{}", name, USAGE)),
+                _ if path.is_none() => path = Some(arg),
+                _ => return Err(anyhow!("unexpected argument {}\n{}", arg, USAGE)),
+            }
+        }
+
+        if let Some(path) = path {
+            options.path = path;
+        }
+
+        // regrouping a sum or product changes how `f64` rounds it, so floats only get their constants folded
+        options.opt_level = match (options.number, opt_level) {
+            (Backend::Float, None) => OptLevel::O1,
+            (Backend::Float, Some(OptLevel::O2)) => {
+                return Err(anyhow!("-O2 needs --number decimal or rational, its rules change how f64 rounds"))
+            }
+            (_, level) => level.unwrap_or(options.opt_level),
+        };
+
+        Ok(options)
+    }
+}
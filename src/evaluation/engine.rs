@@ -0,0 +1,91 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    analyzer::token::{self, BinOps, UnOps},
+    evaluation::number::{Number, Unrepresentable},
+};
+
+/// Functions the interpreter knows how to evaluate, resolved from their name once.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Builtin {
+    Min,
+    Max,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+
+    /// The first smallest or the last largest argument, like `Iterator::min` and `Iterator::max` pick them.
+    /// Arguments that do not compare, like NaN, are only picked if they come first.
+    pub fn apply<N: Number>(self, arguments: &[N]) -> Result<N> {
+        let value = arguments.iter().reduce(|picked, argument| match self {
+            Self::Min if argument < picked => argument,
+            Self::Max if argument >= picked => argument,
+            _ => picked,
+        });
+
+        value
+            .cloned()
+            .ok_or_else(|| anyhow!("function `{}` needs at least one argument", self.name()))
+    }
+}
+
+pub fn unary<N: Number>(op: &UnOps, a: &N) -> N {
+    match op {
+        UnOps::Plus => a.clone(),
+        UnOps::Minus => a.negate(),
+    }
+}
+
+pub fn binary<N: Number>(op: &BinOps, a: &N, b: &N) -> Result<N> {
+    let value = match op {
+        BinOps::Plus => a.checked_add(b),
+        BinOps::Minus => a.checked_sub(b),
+        BinOps::Mult => a.checked_mul(b),
+        BinOps::Div if N::FINITE && b.is_zero() => return Err(anyhow!("division by zero in {} / {}", a, b)),
+        BinOps::Div => a.checked_div(b),
+        BinOps::Mod if N::FINITE && b.is_zero() => return Err(anyhow!("modulo by zero in {} % {}", a, b)),
+        BinOps::Mod => a.checked_rem(b),
+        BinOps::Pow => a.checked_pow(b),
+    };
+
+    value.map_err(|e| match e {
+        Unrepresentable::OutOfRange => anyhow!("{} {} {} is out of range", a, op, b),
+        Unrepresentable::Inexact => anyhow!(
+            "{} {} {} would lose precision, numbers too large for `Decimal` only combine with whole numbers",
+            a, op, b
+        ),
+    })
+}
+
+/// Computes the value of a single node from the values of its operands, without side effects.
+pub fn apply<N: Number>(expression: &token::Kind, arguments: &[N]) -> Result<N> {
+    match expression {
+        token::Kind::Number(number) => N::parse(number).ok_or_else(|| anyhow!("\"{}\" is not a valid number", number)),
+
+        token::Kind::Variable(name) => Err(anyhow!("variable `{}` has no value", name)),
+
+        token::Kind::Func(name) => Builtin::from_name(name)
+            .ok_or_else(|| anyhow!("unknown function `{}`", name))?
+            .apply(arguments),
+
+        token::Kind::UnaryOperator(op) => Ok(unary(op, &arguments[0])),
+
+        token::Kind::BinaryOperator(op) => binary(op, &arguments[0], &arguments[1]),
+
+        _ => Err(anyhow!("can not evaluate `{}`", expression)),
+    }
+}
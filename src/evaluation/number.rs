@@ -0,0 +1,447 @@
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use num_bigint::{BigInt, Sign};
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+use rust_decimal::{Decimal, MathematicalOps};
+
+use crate::evaluation::settings::{Precision, RoundingMode};
+
+/// Bits a big integer, or a numerator or denominator of a `BigRational`, may have after a power. Larger
+/// powers are out of range.
+pub const POWER_BITS: u64 = 1 << 16;
+
+/// Why an operation has no value.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Unrepresentable {
+    /// The value is too large or too small for the backend.
+    OutOfRange,
+    /// The value could only be given rounded, and the backend refuses to round it.
+    Inexact,
+}
+
+/// Values the engine computes with. Operations fail for results the backend can not represent.
+pub trait Number: Clone + PartialOrd + Display + Debug + Send + Sync + 'static {
+    /// Whether every value is finite. Division by zero is an error for finite backends, the others
+    /// give an infinity or NaN.
+    const FINITE: bool;
+
+    fn parse(literal: &str) -> Option<Self>;
+    fn is_zero(&self) -> bool;
+    fn negate(&self) -> Self;
+    fn checked_add(&self, rhs: &Self) -> Result<Self, Unrepresentable>;
+    fn checked_sub(&self, rhs: &Self) -> Result<Self, Unrepresentable>;
+    fn checked_mul(&self, rhs: &Self) -> Result<Self, Unrepresentable>;
+    fn checked_div(&self, rhs: &Self) -> Result<Self, Unrepresentable>;
+    fn checked_rem(&self, rhs: &Self) -> Result<Self, Unrepresentable>;
+    fn checked_pow(&self, exponent: &Self) -> Result<Self, Unrepresentable>;
+    /// Rounds to `precision`, which is never `Precision::Full`.
+    fn round(&self, precision: Precision, mode: RoundingMode) -> Self;
+}
+
+/// The default backend: 96 bit integers scaled by a power of ten, exact for up to 28 significant digits.
+impl Number for Decimal {
+    const FINITE: bool = true;
+
+    fn parse(literal: &str) -> Option<Self> {
+        Decimal::from_str(literal).ok()
+    }
+
+    fn is_zero(&self) -> bool {
+        Decimal::is_zero(self)
+    }
+
+    fn negate(&self) -> Self {
+        -*self
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Decimal::checked_add(*self, *rhs).ok_or(Unrepresentable::OutOfRange)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Decimal::checked_sub(*self, *rhs).ok_or(Unrepresentable::OutOfRange)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Decimal::checked_mul(*self, *rhs).ok_or(Unrepresentable::OutOfRange)
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Decimal::checked_div(*self, *rhs).ok_or(Unrepresentable::OutOfRange)
+    }
+
+    fn checked_rem(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Decimal::checked_rem(*self, *rhs).ok_or(Unrepresentable::OutOfRange)
+    }
+
+    fn checked_pow(&self, exponent: &Self) -> Result<Self, Unrepresentable> {
+        self.checked_powd(*exponent).ok_or(Unrepresentable::OutOfRange)
+    }
+
+    fn round(&self, precision: Precision, mode: RoundingMode) -> Self {
+        match precision {
+            Precision::Full => *self,
+            Precision::Places(places) => self.round_dp_with_strategy(places, mode.strategy()),
+            Precision::Significant(digits) => self.round_sf_with_strategy(digits, mode.strategy()).unwrap_or(*self),
+        }
+    }
+}
+
+/// Fast backend with a range up to about `1e308`, overflowing to infinities and giving NaN for undefined
+/// results such as `0 / 0`. Most decimal fractions, like `0.1`, are not exact.
+impl Number for f64 {
+    const FINITE: bool = false;
+
+    fn parse(literal: &str) -> Option<Self> {
+        f64::from_str(literal).ok()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0.0
+    }
+
+    fn negate(&self) -> Self {
+        -self
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self + rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self - rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self * rhs)
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self / rhs)
+    }
+
+    fn checked_rem(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self % rhs)
+    }
+
+    fn checked_pow(&self, exponent: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self.powf(*exponent))
+    }
+
+    /// Rounds in decimal, values outside the range of `Decimal` are left as they are.
+    fn round(&self, precision: Precision, mode: RoundingMode) -> Self {
+        Decimal::from_f64(*self)
+            .and_then(|value| Number::round(&value, precision, mode).to_f64())
+            .unwrap_or(*self)
+    }
+}
+
+/// Rounds to a whole number.
+fn round_rational(value: &BigRational, mode: RoundingMode) -> BigRational {
+    match mode {
+        RoundingMode::HalfEven => {
+            let floor = value.floor();
+            let half = BigRational::new(1.into(), 2.into());
+
+            match (value - &floor).cmp(&half) {
+                std::cmp::Ordering::Less => floor,
+                std::cmp::Ordering::Greater => floor + BigInt::from(1),
+                std::cmp::Ordering::Equal if floor.to_integer().bit(0) => floor + BigInt::from(1),
+                std::cmp::Ordering::Equal => floor,
+            }
+        }
+        RoundingMode::HalfUp => value.round(),
+        RoundingMode::TowardZero => value.trunc(),
+        RoundingMode::Floor => value.floor(),
+        RoundingMode::Ceil => value.ceil(),
+    }
+}
+
+/// Digits of the whole part of a non-zero `value`, `0` for `0.5` and `-1` for `0.05`.
+fn magnitude(value: &BigRational) -> i64 {
+    let value = value.abs();
+    let (numer, denom) = (value.numer(), value.denom());
+    let digits = |n: &BigInt| n.to_string().len() as i64;
+
+    // `numer / denom` has about as many digits as the difference, one more or one less
+    let estimate = digits(numer) - digits(denom);
+    let ten = BigRational::from_integer(10.into());
+
+    if value >= ten.pow(estimate as i32) {
+        estimate + 1
+    } else {
+        estimate
+    }
+}
+
+/// Exact backend: fractions of integers of any size, so `1 / 3 * 3` is `1`. Powers with a fractional
+/// exponent can not be exact and are computed with `f64`.
+impl Number for BigRational {
+    const FINITE: bool = true;
+
+    /// Reads whole numbers, decimal fractions like `-1.25` and fractions like `1/3`.
+    fn parse(literal: &str) -> Option<Self> {
+        if let Some((numer, denom)) = literal.split_once('/') {
+            let (numer, denom) = (BigInt::from_str(numer.trim()).ok()?, BigInt::from_str(denom.trim()).ok()?);
+            return (!denom.is_zero()).then(|| BigRational::new(numer, denom));
+        }
+
+        let (whole, fraction) = literal.split_once('.').unwrap_or((literal, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return None;
+        }
+
+        let numer = BigInt::from_str(&format!("{}{}", whole, fraction)).ok()?;
+        let denom = BigInt::from(10).pow(fraction.len() as u32);
+
+        Some(BigRational::new(numer, denom))
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+
+    fn negate(&self) -> Self {
+        -self
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self + rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self - rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        Ok(self * rhs)
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        (!Zero::is_zero(rhs)).then(|| self / rhs).ok_or(Unrepresentable::OutOfRange)
+    }
+
+    fn checked_rem(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        (!Zero::is_zero(rhs)).then(|| self % rhs).ok_or(Unrepresentable::OutOfRange)
+    }
+
+    fn checked_pow(&self, exponent: &Self) -> Result<Self, Unrepresentable> {
+        if !exponent.is_integer() {
+            let value = self.to_f64().zip(exponent.to_f64()).map(|(base, exponent)| base.powf(exponent));
+            return value.and_then(BigRational::from_float).ok_or(Unrepresentable::OutOfRange);
+        }
+
+        let exponent = exponent.to_integer();
+
+        // powers of 0, 1 and -1 stay that small however large the exponent is
+        if Zero::is_zero(self) || self.abs().is_one() {
+            return match exponent.sign() {
+                Sign::Minus if Zero::is_zero(self) => Err(Unrepresentable::OutOfRange),
+                Sign::NoSign => Ok(BigRational::one()),
+                _ if Zero::is_zero(self) => Ok(BigRational::zero()),
+                // two's complement keeps the lowest bit of odd negative exponents set
+                _ if self.is_negative() && exponent.bit(0) => Ok(-BigRational::one()),
+                _ => Ok(BigRational::one()),
+            };
+        }
+
+        let exponent = exponent.to_i32().ok_or(Unrepresentable::OutOfRange)?;
+
+        let bits = self.numer().bits().max(self.denom().bits());
+        if bits.saturating_mul(exponent.unsigned_abs() as u64) > POWER_BITS {
+            return Err(Unrepresentable::OutOfRange);
+        }
+
+        Ok(self.pow(exponent))
+    }
+
+    fn round(&self, precision: Precision, mode: RoundingMode) -> Self {
+        let places = match precision {
+            Precision::Full => return self.clone(),
+            Precision::Places(places) => places as i64,
+            Precision::Significant(_) if Zero::is_zero(self) => return self.clone(),
+            Precision::Significant(digits) => digits as i64 - magnitude(self),
+        };
+
+        let scale = BigRational::from_integer(10.into()).pow(places as i32);
+        round_rational(&(self * &scale), mode) / scale
+    }
+}
+
+/// `Decimal` that turns into a big integer when an operation on whole numbers leaves its range, so
+/// `5 ^ 50` and `2 ^ 100 + 1` are exact. Big integers only combine with whole numbers: an operation that
+/// would have to round one, like `2 ^ 100 / 3` or `2 ^ 100 * 0.5`, fails with `Unrepresentable::Inexact`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Promoting {
+    Decimal(Decimal),
+    /// Always outside the range of `Decimal`, whole numbers that fit are kept as `Decimal`.
+    Big(BigInt),
+}
+
+impl Promoting {
+    /// Keeps `value` as `Decimal` if it fits.
+    pub fn from_big(value: BigInt) -> Self {
+        match value.to_i128().and_then(Decimal::from_i128) {
+            Some(value) => Self::Decimal(value),
+            None => Self::Big(value),
+        }
+    }
+
+    /// The value as a big integer, if it is whole.
+    fn whole(&self) -> Option<BigInt> {
+        match self {
+            Self::Decimal(value) if value.is_integer() => value.to_i128().map(BigInt::from),
+            Self::Decimal(_) => None,
+            Self::Big(value) => Some(value.clone()),
+        }
+    }
+
+    /// Computes with `decimal` while both operands are `Decimal`, and with `big` once that overflows for
+    /// whole operands or either operand already is a big integer.
+    fn promote(
+        &self,
+        rhs: &Self,
+        decimal: impl Fn(&Decimal, &Decimal) -> Result<Decimal, Unrepresentable>,
+        big: impl Fn(&BigInt, &BigInt) -> Result<BigInt, Unrepresentable>,
+    ) -> Result<Self, Unrepresentable> {
+        if let (Self::Decimal(a), Self::Decimal(b)) = (self, rhs) {
+            let error = match decimal(a, b) {
+                Ok(value) => return Ok(Self::Decimal(value)),
+                Err(error) => error,
+            };
+
+            return match (self.whole(), rhs.whole()) {
+                (Some(a), Some(b)) => big(&a, &b).map(Self::from_big).map_err(|_| error),
+                _ => Err(error),
+            };
+        }
+
+        match (self.whole(), rhs.whole()) {
+            (Some(a), Some(b)) => big(&a, &b).map(Self::from_big),
+            _ => Err(Unrepresentable::Inexact),
+        }
+    }
+}
+
+impl Display for Promoting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Decimal(value) => Display::fmt(value, f),
+            Self::Big(value) => Display::fmt(value, f),
+        }
+    }
+}
+
+impl PartialOrd for Promoting {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Decimal(a), Self::Decimal(b)) => a.partial_cmp(b),
+            (Self::Big(a), Self::Big(b)) => a.partial_cmp(b),
+            // a big integer is beyond every `Decimal`, so only its sign matters
+            (Self::Big(a), Self::Decimal(_)) => Some(if a.is_positive() { Ordering::Greater } else { Ordering::Less }),
+            (Self::Decimal(_), Self::Big(b)) => Some(if b.is_positive() { Ordering::Less } else { Ordering::Greater }),
+        }
+    }
+}
+
+/// The `decimal` backend of the command line: `Decimal`, with big integers for whole results too large for it.
+impl Number for Promoting {
+    const FINITE: bool = true;
+
+    /// Reads what `Decimal` reads, and whole numbers of any size.
+    fn parse(literal: &str) -> Option<Self> {
+        match Decimal::from_str(literal) {
+            Ok(value) => Some(Self::Decimal(value)),
+            Err(_) if !literal.contains('.') => BigInt::from_str(literal).ok().map(Self::from_big),
+            Err(_) => None,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        matches!(self, Self::Decimal(value) if value.is_zero())
+    }
+
+    fn negate(&self) -> Self {
+        match self {
+            Self::Decimal(value) => Self::Decimal(-*value),
+            Self::Big(value) => Self::Big(-value),
+        }
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        self.promote(rhs, Number::checked_add, |a, b| Ok(a + b))
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        self.promote(rhs, Number::checked_sub, |a, b| Ok(a - b))
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        self.promote(rhs, Number::checked_mul, |a, b| Ok(a * b))
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        self.promote(rhs, Number::checked_div, |a, b| match a % b {
+            remainder if remainder.is_zero() => Ok(a / b),
+            _ => Err(Unrepresentable::Inexact),
+        })
+    }
+
+    fn checked_rem(&self, rhs: &Self) -> Result<Self, Unrepresentable> {
+        self.promote(rhs, Number::checked_rem, |a, b| Ok(a % b))
+    }
+
+    fn checked_pow(&self, exponent: &Self) -> Result<Self, Unrepresentable> {
+        self.promote(exponent, Number::checked_pow, |base, exponent| {
+            if exponent.is_negative() {
+                return Err(Unrepresentable::Inexact);
+            }
+
+            let exponent = exponent.to_u32().ok_or(Unrepresentable::OutOfRange)?;
+            if base.bits().saturating_mul(exponent as u64) > POWER_BITS {
+                return Err(Unrepresentable::OutOfRange);
+            }
+
+            Ok(base.pow(exponent))
+        })
+    }
+
+    fn round(&self, precision: Precision, mode: RoundingMode) -> Self {
+        match self {
+            Self::Decimal(value) => Self::Decimal(Number::round(value, precision, mode)),
+            Self::Big(value) => {
+                let value = Number::round(&BigRational::from_integer(value.clone()), precision, mode);
+                Self::from_big(value.to_integer())
+            }
+        }
+    }
+}
+
+/// Which `Number` implementation evaluates an expression.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Backend {
+    /// `Promoting`, so whole numbers too large for `Decimal` stay exact.
+    #[default]
+    Decimal,
+    Float,
+    Rational,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "decimal" => Ok(Self::Decimal),
+            "f64" => Ok(Self::Float),
+            "rational" => Ok(Self::Rational),
+            _ => Err(anyhow!("unknown number backend \"{}\", expected decimal, f64 or rational", s)),
+        }
+    }
+}
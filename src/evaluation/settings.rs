@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use rust_decimal::RoundingStrategy;
+
+use crate::{analyzer::limits::Limits, evaluation::number::Number};
+
+/// How many digits of a value are kept.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Precision {
+    /// Every digit `Decimal` has, up to 28.
+    #[default]
+    Full,
+    /// Digits after the decimal point.
+    Places(u32),
+    /// Significant digits, counted from the first one that is not zero.
+    Significant(u32),
+}
+
+/// Which way a value is rounded when digits are dropped.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum RoundingMode {
+    /// Halfway values go to the even neighbour, `2.5` to `2` and `3.5` to `4`.
+    #[default]
+    HalfEven,
+    /// Halfway values go away from zero, `2.5` to `3` and `-2.5` to `-3`.
+    HalfUp,
+    TowardZero,
+    Floor,
+    Ceil,
+}
+
+impl RoundingMode {
+    pub fn strategy(self) -> RoundingStrategy {
+        match self {
+            Self::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Self::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Self::TowardZero => RoundingStrategy::ToZero,
+            Self::Floor => RoundingStrategy::ToNegativeInfinity,
+            Self::Ceil => RoundingStrategy::ToPositiveInfinity,
+        }
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-even" => Ok(Self::HalfEven),
+            "half-up" => Ok(Self::HalfUp),
+            "toward-zero" => Ok(Self::TowardZero),
+            "floor" => Ok(Self::Floor),
+            "ceil" => Ok(Self::Ceil),
+            _ => Err(anyhow!(
+                "unknown rounding mode \"{}\", expected half-even, half-up, toward-zero, floor or ceil",
+                s
+            )),
+        }
+    }
+}
+
+/// When values are rounded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum RoundAt {
+    /// Only the result, every operation works with all digits.
+    #[default]
+    Output,
+    /// The value of every operator and function call, so later operations only see the rounded value.
+    EveryStep,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Rounding {
+    pub precision: Precision,
+    pub mode:      RoundingMode,
+    pub at:        RoundAt,
+}
+
+impl Rounding {
+    pub fn round<N: Number>(&self, value: N) -> N {
+        match self.precision {
+            Precision::Full => value,
+            precision => value.round(precision, self.mode),
+        }
+    }
+
+    /// Rounds the value of an operator or function call if every step is rounded.
+    pub fn step<N: Number>(&self, value: N) -> N {
+        match self.at {
+            RoundAt::Output => value,
+            RoundAt::EveryStep => self.round(value),
+        }
+    }
+}
+
+/// Everything an evaluation can be configured with. A session keeps one and passes it, or a changed
+/// copy, along with every call.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Settings {
+    pub limits:   Limits,
+    pub rounding: Rounding,
+}
@@ -0,0 +1,435 @@
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    io::{Read, Write},
+    iter,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, PoisonError,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    analyzer::syntax_tree::SyntaxTree,
+    evaluation::{number::Number, settings::Settings},
+    interpreter::{
+        bindings::Bindings,
+        bytecode::{self, Vm},
+    },
+};
+
+/// Header of the column holding the value of every row.
+pub const RESULT_COLUMN: &str = "result";
+/// Header of the column holding the error of every row that failed to evaluate.
+pub const ERROR_COLUMN: &str = "error";
+
+/// Rows of a CSV input that a worker takes at a time.
+const BATCH_ROWS: usize = 256;
+/// Batches per worker that may be read but not yet written.
+const IN_FLIGHT_BATCHES: usize = 4;
+
+/// One worker thread per available core.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Text of a panic payload, which is a `&str` or a `String` unless the panic was raised with another value.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload.downcast_ref::<String>().map_or("unknown panic", String::as_str),
+    }
+}
+
+/// Runs `evaluate` on the worker state, turning a panic into an error of this item alone. The state may have
+/// been left half updated, so it is made anew by `init`.
+fn guarded<S, R>(state: &mut S, init: &impl Fn() -> S, evaluate: impl FnOnce(&mut S) -> Result<R>) -> Result<R> {
+    match panic::catch_unwind(AssertUnwindSafe(|| evaluate(state))) {
+        Ok(result) => result,
+        Err(payload) => {
+            *state = init();
+            Err(anyhow!("evaluation panicked: {}", panic_message(payload.as_ref())))
+        }
+    }
+}
+
+/// Applies `evaluate` to every item on up to `threads` worker threads, each with its own state made by `init`.
+/// Workers take the next item from a shared counter, so one slow item does not hold up the others, and every
+/// result is stored at the index of its item, so the order of the results does not depend on scheduling. An
+/// item whose evaluation panics gets an error as its result.
+fn parallel_map<T, S, R>(
+    items: &[T],
+    threads: usize,
+    init: impl Fn() -> S + Sync,
+    evaluate: impl Fn(&mut S, &T) -> Result<R> + Sync,
+) -> Vec<Result<R>>
+where
+    T: Sync,
+    R: Send,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+
+    if threads == 1 {
+        let mut state = init();
+        return items.iter().map(|item| guarded(&mut state, &init, |state| evaluate(state, item))).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<Result<R>>> = (0..items.len()).map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut state = init();
+                    let mut done = vec![];
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(index) {
+                            Some(item) => done.push((index, guarded(&mut state, &init, |state| evaluate(state, item)))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // a worker that died anyway, e.g. in `init`, leaves the results of its items missing
+        for worker in workers {
+            for (index, result) in worker.join().unwrap_or_default() {
+                results[index] = Some(result);
+            }
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(anyhow!("worker thread panicked"))))
+        .collect()
+}
+
+/// Evaluates every tree with the same `bindings` on up to `threads` threads, limited and rounded as `settings`
+/// say for every tree on its own. The results are in the order of `trees`, and a tree failing to compile or
+/// evaluate only fails its own result.
+pub fn evaluate_trees<N: Number>(
+    trees: &[SyntaxTree],
+    bindings: &Bindings<N>,
+    threads: usize,
+    settings: &Settings,
+) -> Vec<Result<N>> {
+    parallel_map(trees, threads, || Vm::with_settings(settings), |vm, tree| {
+        bytecode::compile(tree).and_then(|program| vm.run(&program, bindings))
+    })
+}
+
+/// Evaluates `tree` once for every set of bindings in `rows` on up to `threads` threads, limited and rounded as
+/// `settings` say for every row on its own. The results are in the order of `rows`, and a row failing to
+/// evaluate only fails its own result. Fails as a whole only if `tree` can not be compiled.
+pub fn evaluate_rows<N: Number>(
+    tree: &SyntaxTree,
+    rows: &[Bindings<N>],
+    threads: usize,
+    settings: &Settings,
+) -> Result<Vec<Result<N>>> {
+    let program = bytecode::compile(tree)?;
+    let init = || Vm::with_settings(settings);
+
+    Ok(parallel_map(rows, threads, init, |vm, bindings| vm.run(&program, bindings)))
+}
+
+/// How many rows a batch evaluated and how many of them failed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Summary {
+    pub rows:   usize,
+    pub failed: usize,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "evaluated {} rows, {} failed", self.rows, self.failed)
+    }
+}
+
+/// Binds the cells of a row to the variables read from the columns `(variable, column)`. A row needs as many
+/// cells as the header has.
+fn bind_row<N: Number>(
+    record: &csv::StringRecord,
+    width: usize,
+    columns: &[(String, usize)],
+    bindings: &mut Bindings<N>,
+) -> Result<()> {
+    if record.len() != width {
+        return Err(anyhow!("expected {} fields like the header, got {}", width, record.len()));
+    }
+
+    for (name, column) in columns {
+        let cell = &record[*column];
+        let value = N::parse(cell).ok_or_else(|| anyhow!("column `{}`: \"{}\" is not a valid number", name, cell))?;
+
+        bindings.set(name.as_str(), value);
+    }
+
+    Ok(())
+}
+
+/// Evaluates `tree` once for every row of the CSV `input`, whose header names the variable every column binds.
+/// Variables without a column take their value from `defaults`. Every row is written to `output` as it was read,
+/// followed by a `result` and an `error` column, one of which is empty. Rows are streamed, so the input never has
+/// to fit into memory: up to `threads` workers, started once for the whole input, take batches of rows while the
+/// calling thread reads the next ones and writes the evaluated ones back in the order they were read. Every row
+/// is limited and rounded as `settings` say. A row failing to evaluate, going over a limit, panicking or with
+/// more or fewer fields than the header, is only reported in its error column; such a row is written with
+/// exactly as many fields as the header. Input that is not CSV at all fails the batch after the rows before it
+/// are written, a variable bound by neither the input nor `defaults` fails it before any row.
+pub fn evaluate_csv<N: Number, R: Read, W: Write>(
+    tree: &SyntaxTree,
+    defaults: &Bindings<N>,
+    input: R,
+    output: W,
+    threads: usize,
+    settings: &Settings,
+) -> Result<Summary> {
+    let program = bytecode::compile::<N>(tree)?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(input);
+    let mut writer = csv::Writer::from_writer(output);
+
+    let headers = reader.headers()?.clone();
+    let mut columns = Vec::with_capacity(program.variables().len());
+
+    for name in program.variables() {
+        match headers.iter().position(|header| header == name) {
+            Some(column) => columns.push((name.clone(), column)),
+            None if defaults.get(name).is_some() => {}
+            None => return Err(anyhow!("variable `{}` is neither a column of the input nor set", name)),
+        }
+    }
+
+    writer.write_record(headers.iter().chain([RESULT_COLUMN, ERROR_COLUMN]))?;
+
+    let threads = threads.max(1);
+    let init = || (Vm::with_settings(settings), defaults.clone());
+    let evaluate = |(vm, bindings): &mut (Vm<N>, Bindings<N>), record: &csv::StringRecord| {
+        bind_row(record, headers.len(), &columns, bindings).and_then(|()| vm.run(&program, bindings))
+    };
+
+    // only the workers share the queue, so sending fails instead of blocking once all of them are gone
+    let (batches, queue) = mpsc::sync_channel::<(usize, Arc<Vec<csv::StringRecord>>)>(threads);
+    let queue = Arc::new(Mutex::new(queue));
+    let (done, evaluated) = mpsc::channel::<(usize, Vec<Result<N>>)>();
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let (queue, done) = (Arc::clone(&queue), done.clone());
+            let (init, evaluate) = (&init, &evaluate);
+
+            scope.spawn(move || {
+                let mut state = init();
+
+                // the lock is only held while waiting for the next batch
+                while let Ok((index, batch)) = queue.lock().unwrap_or_else(PoisonError::into_inner).recv() {
+                    let values = batch
+                        .iter()
+                        .map(|record| guarded(&mut state, init, |state| evaluate(state, record)))
+                        .collect();
+
+                    if done.send((index, values)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop((queue, done));
+
+        let mut output = Output {
+            writer:    &mut writer,
+            width:     headers.len(),
+            pending:   BTreeMap::new(),
+            evaluated: BTreeMap::new(),
+            next:      0,
+            summary:   Summary::default(),
+        };
+        let mut failure = None;
+
+        for index in 0.. {
+            // a slow batch holds up writing the ones after it, so reading waits for it after a while
+            while output.pending.len() >= IN_FLIGHT_BATCHES * threads {
+                match evaluated.recv() {
+                    Ok(finished) => output.write_ready(iter::once(finished))?,
+                    Err(_) => break,
+                }
+            }
+
+            let mut batch = Vec::with_capacity(BATCH_ROWS);
+
+            while batch.len() < BATCH_ROWS {
+                let mut record = csv::StringRecord::new();
+
+                match reader.read_record(&mut record) {
+                    Ok(true) => batch.push(record),
+                    Ok(false) => break,
+                    Err(e) => {
+                        failure = Some(e.into());
+                        break;
+                    }
+                }
+            }
+
+            let last = batch.len() < BATCH_ROWS;
+
+            if !batch.is_empty() {
+                let batch = Arc::new(batch);
+                output.pending.insert(index, Arc::clone(&batch));
+
+                // fails only if every worker is gone, their batches are written with an error below
+                let _ = batches.send((index, batch));
+            }
+
+            if let Err(e) = output.write_ready(evaluated.try_iter()) {
+                failure = Some(e);
+            }
+
+            if last || failure.is_some() {
+                break;
+            }
+        }
+
+        // let the workers finish the batches sent so far and stop
+        drop(batches);
+        let written = output.write_ready(evaluated.iter()).and_then(|()| output.write_lost());
+
+        let summary = output.summary;
+        writer.flush()?;
+
+        match failure.or(written.err()) {
+            Some(e) => Err(e),
+            None => Ok(summary),
+        }
+    })
+}
+
+/// Writes evaluated batches of `evaluate_csv` in the order they were read. `pending` keeps the rows of every
+/// batch sent to the workers until it is written, `evaluated` holds the values of batches that came back ahead
+/// of an earlier one.
+struct Output<'a, N, W: Write> {
+    writer:    &'a mut csv::Writer<W>,
+    width:     usize,
+    pending:   BTreeMap<usize, Arc<Vec<csv::StringRecord>>>,
+    evaluated: BTreeMap<usize, Vec<Result<N>>>,
+    next:      usize,
+    summary:   Summary,
+}
+
+impl<N: Number, W: Write> Output<'_, N, W> {
+    /// Takes the batches the workers finished and writes every one whose predecessors are all written.
+    fn write_ready(&mut self, finished: impl Iterator<Item = (usize, Vec<Result<N>>)>) -> Result<()> {
+        for (index, values) in finished {
+            self.evaluated.insert(index, values);
+
+            while let Some(values) = self.evaluated.remove(&self.next) {
+                let rows = self.pending.remove(&self.next).unwrap();
+                self.write(&rows, values)?;
+                self.next += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the batches a worker took and never finished, which only happens if it died, with an error for
+    /// every row. The batches that did come back are written in between.
+    fn write_lost(&mut self) -> Result<()> {
+        while let Some((index, rows)) = self.pending.pop_first() {
+            let values = match self.evaluated.remove(&index) {
+                Some(values) => values,
+                None => rows.iter().map(|_| Err(anyhow!("worker thread panicked"))).collect(),
+            };
+
+            self.write(&rows, values)?;
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, rows: &[csv::StringRecord], values: Vec<Result<N>>) -> Result<()> {
+        for (record, value) in rows.iter().zip(values) {
+            let (result, error) = match value {
+                Ok(value) => (value.to_string(), String::new()),
+                Err(e) => {
+                    self.summary.failed += 1;
+                    (String::new(), e.to_string())
+                }
+            };
+
+            let cells = record.iter().chain(std::iter::repeat("")).take(self.width);
+            self.writer.write_record(cells.chain([result.as_str(), error.as_str()]))?;
+            self.summary.rows += 1;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{lexer, parser};
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn a_panic_only_fails_its_own_item() {
+        let items: Vec<usize> = (0..100).collect();
+
+        for threads in [1, 4] {
+            let results = parallel_map(&items, threads, || (), |_, item| match item {
+                13 => panic!("unlucky"),
+                _ => Ok(item * 2),
+            });
+
+            for (item, result) in items.iter().zip(results) {
+                match item {
+                    13 => assert_eq!(result.unwrap_err().to_string(), "evaluation panicked: unlucky"),
+                    _ => assert_eq!(result.unwrap(), item * 2),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn csv_rows_come_out_in_input_order() {
+        let tree = parser::parse(lexer::tokenize("x * 2".chars()).unwrap()).unwrap();
+        let rows = 10 * BATCH_ROWS * IN_FLIGHT_BATCHES + 7;
+
+        let mut input = String::from("x\n");
+        let mut expected = String::from("x,result,error\n");
+        for row in 0..rows {
+            match row {
+                500 => {
+                    input.push_str("1,2\n");
+                    expected.push_str("1,,\"expected 1 fields like the header, got 2\"\n");
+                }
+                _ => {
+                    input.push_str(&format!("{}\n", row));
+                    expected.push_str(&format!("{},{},\n", row, row * 2));
+                }
+            }
+        }
+
+        let mut output = vec![];
+        let bindings = Bindings::<Decimal>::default();
+        let summary = evaluate_csv(&tree, &bindings, input.as_bytes(), &mut output, 3, &Settings::default()).unwrap();
+
+        assert_eq!(summary, Summary { rows, failed: 1 });
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use crate::evaluation::number::Number;
+
+/// Values of the variables an expression is evaluated with.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bindings<N: Number = Decimal> {
+    values: HashMap<String, N>,
+}
+
+impl<N: Number> Default for Bindings<N> {
+    fn default() -> Self {
+        Self { values: HashMap::new() }
+    }
+}
+
+impl<N: Number> Bindings<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: N) {
+        self.values.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&N> {
+        self.values.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Value of `name`, or an error naming the unbound variable.
+    pub fn lookup(&self, name: &str) -> Result<N> {
+        self.get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("variable `{}` has no value", name))
+    }
+
+    /// Parses a `name=value` assignment.
+    pub fn parse_assignment(assignment: &str) -> Result<(String, N)> {
+        let (name, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected name=value, got \"{}\"", assignment))?;
+        let (name, value) = (name.trim(), value.trim());
+
+        if !name.chars().next().is_some_and(char::is_alphabetic) || !name.chars().all(char::is_alphanumeric) {
+            return Err(anyhow!("\"{}\" is not a variable name", name));
+        }
+
+        let value = N::parse(value).ok_or_else(|| anyhow!("\"{}\" is not a valid number", value))?;
+        Ok((name.to_string(), value))
+    }
+}
+
+impl<N: Number> FromIterator<(String, N)> for Bindings<N> {
+    fn from_iter<T: IntoIterator<Item = (String, N)>>(iter: T) -> Self {
+        Self {
+            values: iter.into_iter().collect(),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    fmt::{Display, Formatter},
+    ops::ControlFlow,
+};
+
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use crate::{
+    analyzer::{
+        diagnostic::Diagnostic,
+        syntax_tree::{NodeId, SyntaxTree},
+        token::{self, BinOps, Span, UnOps},
+        visitor::Visitor,
+    },
+    evaluation::{
+        engine::{self, Builtin},
+        number::Number,
+        settings::Settings,
+    },
+    interpreter::bindings::Bindings,
+};
+
+/// Operation of the stack machine. Operands are popped from the top of the stack and the result is pushed back.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Instruction {
+    /// Pushes the constant with the given index.
+    Const(u32),
+    /// Pushes the value of the variable with the given slot.
+    Load(u32),
+    Unary(UnOps),
+    Binary(BinOps),
+    /// Calls the function with the given number of arguments.
+    Call(Builtin, u32),
+}
+
+/// Syntax tree lowered to instructions in evaluation order: operands left to right, then the node using them.
+#[derive(Debug, Clone)]
+pub struct Program<N: Number = Decimal> {
+    code:      Vec<Instruction>,
+    /// Source position of every instruction, to report evaluation errors.
+    spans:     Vec<Span>,
+    constants: Vec<N>,
+    /// Variable name of every slot.
+    variables: Vec<String>,
+    max_stack: usize,
+}
+
+impl<N: Number> Program<N> {
+    pub fn code(&self) -> &[Instruction] {
+        &self.code
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+}
+
+impl<N: Number> Display for Program<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (pc, instruction) in self.code.iter().enumerate() {
+            write!(f, "{:04}  ", pc)?;
+
+            match instruction {
+                Instruction::Const(index) => writeln!(f, "const {}", self.constants[*index as usize])?,
+                Instruction::Load(slot) => writeln!(f, "load  {}", self.variables[*slot as usize])?,
+                Instruction::Unary(op) => writeln!(f, "unary {}", op)?,
+                Instruction::Binary(op) => writeln!(f, "op    {}", op)?,
+                Instruction::Call(function, arguments) => writeln!(f, "call  {}/{}", function.name(), arguments)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Emits the instructions of every node once its operands are emitted.
+struct Compiler<N: Number> {
+    program: Program<N>,
+    depth:   usize,
+}
+
+impl<N: Number> Compiler<N> {
+    fn emit(&mut self, instruction: Instruction, span: Span, pops: usize) {
+        self.program.code.push(instruction);
+        self.program.spans.push(span);
+
+        self.depth = self.depth - pops + 1;
+        self.program.max_stack = self.program.max_stack.max(self.depth);
+    }
+}
+
+impl<N: Number> Visitor for Compiler<N> {
+    type Break = anyhow::Error;
+
+    fn leave(&mut self, tree: &SyntaxTree, node: NodeId) -> ControlFlow<Self::Break> {
+        let value = &tree[node].value;
+        let span = value.span();
+        let arguments = tree.get_children(node).len();
+
+        match &value.kind {
+            token::Kind::Number(number) => match N::parse(number) {
+                Some(constant) => {
+                    self.emit(Instruction::Const(self.program.constants.len() as u32), span, 0);
+                    self.program.constants.push(constant);
+                }
+                None => return ControlFlow::Break(anyhow!("\"{}\" is not a valid number", number)),
+            },
+
+            token::Kind::Variable(name) => {
+                let slot = match self.program.variables.iter().position(|variable| variable == name) {
+                    Some(slot) => slot,
+                    None => {
+                        self.program.variables.push(name.clone());
+                        self.program.variables.len() - 1
+                    }
+                };
+
+                self.emit(Instruction::Load(slot as u32), span, 0);
+            }
+
+            token::Kind::UnaryOperator(op) => self.emit(Instruction::Unary(op.clone()), span, 1),
+
+            token::Kind::BinaryOperator(op) => self.emit(Instruction::Binary(op.clone()), span, 2),
+
+            token::Kind::Func(name) => match Builtin::from_name(name) {
+                Some(function) if arguments > 0 => {
+                    self.emit(Instruction::Call(function, arguments as u32), span, arguments)
+                }
+                Some(_) => {
+                    let message = format!("function `{}` needs at least one argument", name);
+                    return ControlFlow::Break(Diagnostic::at(message, value).into());
+                }
+                None => return ControlFlow::Break(Diagnostic::at(format!("unknown function `{}`", name), value).into()),
+            },
+
+            kind => return ControlFlow::Break(Diagnostic::at(format!("can not compile `{}`", kind), value).into()),
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+/// Lowers `tree` to a program that evaluates to the same value as `interprete` for any bindings. Unknown
+/// functions and calls without arguments are rejected here, before anything is evaluated, so where the
+/// interpreter first fails on an argument of such a call the error differs.
+pub fn compile<N: Number>(tree: &SyntaxTree) -> Result<Program<N>> {
+    if tree.is_empty() {
+        return Err(anyhow!("empty syntax tree"));
+    }
+
+    let mut compiler = Compiler {
+        program: Program {
+            code:      vec![],
+            spans:     vec![],
+            constants: vec![],
+            variables: vec![],
+            max_stack: 0,
+        },
+        depth:   0,
+    };
+
+    match tree.walk(&mut compiler) {
+        ControlFlow::Break(e) => Err(e),
+        ControlFlow::Continue(()) => Ok(compiler.program),
+    }
+}
+
+/// Stack machine running compiled programs. Its buffers are reused, so once they have grown to fit
+/// a program, evaluating it again does not allocate.
+#[derive(Debug)]
+pub struct Vm<N: Number = Decimal> {
+    stack:    Vec<N>,
+    values:   Vec<Option<N>>,
+    settings: Settings,
+}
+
+impl<N: Number> Default for Vm<N> {
+    fn default() -> Self {
+        Self {
+            stack:    vec![],
+            values:   vec![],
+            settings: Settings::default(),
+        }
+    }
+}
+
+impl<N: Number> Vm<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Machine limiting and rounding every run like `interprete_configured` does with the same `settings`.
+    /// Every instruction other than `Const` and `Load` is a step; unlike the interpreter the machine does not
+    /// merge repeated subexpressions, so it may take more steps for the same tree.
+    pub fn with_settings(settings: &Settings) -> Self {
+        Self {
+            settings: *settings,
+            ..Self::default()
+        }
+    }
+
+    pub fn run(&mut self, program: &Program<N>, bindings: &Bindings<N>) -> Result<N> {
+        let rounding = self.settings.rounding;
+        let mut budget = self.settings.limits.budget();
+
+        self.stack.clear();
+        self.stack.reserve(program.max_stack);
+
+        // variables are looked up on first use
+        self.values.clear();
+        self.values.resize(program.variables.len(), None);
+
+        for (pc, instruction) in program.code.iter().enumerate() {
+            let located = |e: anyhow::Error| {
+                let span = program.spans[pc];
+                anyhow::Error::from(Diagnostic::new(e.to_string(), span.line, span.column))
+            };
+
+            if !matches!(instruction, Instruction::Const(_) | Instruction::Load(_)) {
+                budget.step()?;
+            }
+
+            match instruction {
+                Instruction::Const(index) => self.stack.push(program.constants[*index as usize].clone()),
+
+                Instruction::Load(slot) => {
+                    let slot = *slot as usize;
+                    let value = match &self.values[slot] {
+                        Some(value) => value.clone(),
+                        None => {
+                            let value = bindings.lookup(&program.variables[slot]).map_err(located)?;
+                            self.values[slot] = Some(value.clone());
+                            value
+                        }
+                    };
+
+                    self.stack.push(value);
+                }
+
+                Instruction::Unary(op) => {
+                    let a = self.stack.last_mut().unwrap();
+                    *a = rounding.step(engine::unary(op, a));
+                }
+
+                Instruction::Binary(op) => {
+                    let b = self.stack.pop().unwrap();
+                    let a = self.stack.last_mut().unwrap();
+                    *a = rounding.step(engine::binary(op, a, &b).map_err(located)?);
+                }
+
+                Instruction::Call(function, arguments) => {
+                    let start = self.stack.len() - *arguments as usize;
+                    let value = function.apply(&self.stack[start..]).map_err(located)?;
+
+                    self.stack.truncate(start);
+                    self.stack.push(rounding.step(value));
+                }
+            }
+        }
+
+        Ok(rounding.round(self.stack.pop().unwrap()))
+    }
+}
@@ -0,0 +1,152 @@
+use std::marker::PhantomData;
+
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use crate::{
+    analyzer::{
+        diagnostic::Diagnostic,
+        limits::Budget,
+        syntax_tree::{NodeId, SyntaxTree},
+        token::{self, UnOps},
+        visitor::Fold,
+    },
+    evaluation::{
+        engine::{self, Builtin},
+        number::Number,
+        settings::{Rounding, Settings},
+    },
+    interpreter::bindings::Bindings,
+};
+
+/// Expression compiled into a closure, evaluated with the values of its variables.
+pub type Compiled<N = Decimal> = Box<dyn Fn(&Bindings<N>) -> Result<N>>;
+
+/// Closure of a single node, accounting every operator and call it evaluates to the budget of the evaluation.
+type Node<N> = Box<dyn Fn(&Bindings<N>, &mut Budget) -> Result<N>>;
+
+/// Closure that always fails with `message` on the position of `value`, for errors the interpreter
+/// only reports once it gets to evaluate the node.
+fn failing<N: Number>(message: String, value: &token::Token) -> Node<N> {
+    let (line, column) = (value.line, value.column);
+    Box::new(move |_, _| Err(Diagnostic::new(message.clone(), line, column).into()))
+}
+
+/// Composes the closures of the operands into the closure of the node using them.
+struct ClosureBuilder<N> {
+    rounding: Rounding,
+    number:   PhantomData<N>,
+}
+
+impl<N: Number> Fold for ClosureBuilder<N> {
+    type Output = Node<N>;
+
+    fn fold(&mut self, tree: &SyntaxTree, node: NodeId, mut children: Vec<Node<N>>) -> Result<Node<N>> {
+        let value = tree[node].value.clone();
+        let rounding = self.rounding;
+
+        let compiled: Node<N> = match &value.kind {
+            token::Kind::Number(number) => match N::parse(number) {
+                Some(constant) => Box::new(move |_, _| Ok(constant.clone())),
+                None => failing(format!("\"{}\" is not a valid number", number), &value),
+            },
+
+            token::Kind::Variable(name) => {
+                let name = name.clone();
+                Box::new(move |bindings, _| {
+                    bindings
+                        .lookup(&name)
+                        .map_err(|e| Diagnostic::at(e.to_string(), &value).into())
+                })
+            }
+
+            token::Kind::UnaryOperator(UnOps::Plus) => {
+                let operand = children.pop().unwrap();
+                Box::new(move |bindings, budget| {
+                    let a = operand(bindings, budget)?;
+                    budget.step()?;
+                    Ok(rounding.step(a))
+                })
+            }
+            token::Kind::UnaryOperator(UnOps::Minus) => {
+                let operand = children.pop().unwrap();
+                Box::new(move |bindings, budget| {
+                    let a = operand(bindings, budget)?;
+                    budget.step()?;
+                    Ok(rounding.step(a.negate()))
+                })
+            }
+
+            token::Kind::BinaryOperator(op) => {
+                let op = op.clone();
+                let rhs = children.pop().unwrap();
+                let lhs = children.pop().unwrap();
+
+                Box::new(move |bindings, budget| {
+                    let (a, b) = (lhs(bindings, budget)?, rhs(bindings, budget)?);
+                    budget.step()?;
+                    engine::binary(&op, &a, &b)
+                        .map(|value| rounding.step(value))
+                        .map_err(|e| Diagnostic::at(e.to_string(), &value).into())
+                })
+            }
+
+            token::Kind::Func(name) => match Builtin::from_name(name) {
+                Some(function) => Box::new(move |bindings, budget| {
+                    let arguments = children
+                        .iter()
+                        .map(|argument| argument(bindings, budget))
+                        .collect::<Result<Vec<_>>>()?;
+                    budget.step()?;
+                    function
+                        .apply(&arguments)
+                        .map(|value| rounding.step(value))
+                        .map_err(|e| Diagnostic::at(e.to_string(), &value).into())
+                }),
+                None => {
+                    // the interpreter evaluates the arguments before it finds out the function is unknown
+                    let message = format!("unknown function `{}`", name);
+                    Box::new(move |bindings, budget| {
+                        for argument in &children {
+                            argument(bindings, budget)?;
+                        }
+                        budget.step()?;
+                        Err(Diagnostic::at(message.clone(), &value).into())
+                    })
+                }
+            },
+
+            kind => failing(format!("can not evaluate `{}`", kind), &value),
+        };
+
+        Ok(compiled)
+    }
+}
+
+/// Compiles `tree` into nested closures, one per node, that evaluate to the same value or fail with the
+/// same error as `interprete` for any bindings. Evaluation recurses once per level of the tree, so very
+/// deep trees are better evaluated by the interpreter or the bytecode VM.
+pub fn compile<N: Number>(tree: &SyntaxTree) -> Compiled<N> {
+    compile_with(tree, &Settings::default())
+}
+
+/// Like `compile`, limiting and rounding every evaluation like the bytecode VM does with the same `settings`.
+/// Every evaluated operator and function call is a step, repeated subexpressions are evaluated every time.
+pub fn compile_with<N: Number>(tree: &SyntaxTree, settings: &Settings) -> Compiled<N> {
+    let (limits, rounding) = (settings.limits, settings.rounding);
+    let mut builder = ClosureBuilder {
+        rounding,
+        number: PhantomData,
+    };
+
+    match tree.fold(&mut builder) {
+        Ok(compiled) => Box::new(move |bindings| {
+            let value = compiled(bindings, &mut limits.budget())?;
+            Ok(rounding.round(value))
+        }),
+        Err(e) => {
+            let message = e.to_string();
+            Box::new(move |_| Err(anyhow!("{}", message)))
+        }
+    }
+}
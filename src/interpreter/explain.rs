@@ -0,0 +1,85 @@
+use anyhow::Result;
+
+use crate::{
+    analyzer::{
+        dag::Dag,
+        diagnostic::Diagnostic,
+        syntax_tree::SyntaxTree,
+        token::{self, Token},
+        unparser,
+    },
+    evaluation::{engine, number::Number, settings::Settings},
+    interpreter::bindings::Bindings,
+};
+
+/// The whole expression once as given and once after every evaluation step, e.g. `1 + 4 * 2`, `1 + [8]`, `[9]`.
+/// Steps come in the order `interprete` evaluates them, and the literal a step produced is marked as `[value]`.
+/// A subexpression occurring several times is evaluated once, so all of its occurrences are reduced in the same step.
+/// Replacing a variable with its value from `bindings` counts as a step of its own. Values are rounded as
+/// `settings.rounding` says, and if rounding the result changes it, the rounded result is the last line.
+/// Every line is as long as the expression, so like `interprete_configured` every evaluated operator and
+/// function call is a step of `settings.limits`, checked before its line is written.
+pub fn explain<N: Number>(tree: &SyntaxTree, bindings: &Bindings<N>, settings: &Settings) -> Result<Vec<String>> {
+    let rounding = settings.rounding;
+    let mut budget = settings.limits.budget();
+    let dag = Dag::try_from(tree)?;
+    let mut tree = tree.clone();
+
+    let mut occurrences = vec![vec![]; dag.len()];
+    tree.dfs(|node| {
+        if let Some(index) = dag.index_of(node) {
+            occurrences[index].push(node);
+        }
+    });
+
+    let mut values = Vec::<N>::with_capacity(dag.len());
+    let mut lines = vec![unparser::unparse(&tree)?];
+
+    for (node, occurrences) in dag.nodes().iter().zip(occurrences) {
+        if !matches!(node.value.kind, token::Kind::Number(_) | token::Kind::Variable(_)) {
+            budget.step()?;
+        }
+
+        let arguments: Vec<N> = node.children.iter().map(|child| values[*child].clone()).collect();
+        let value = match &node.value.kind {
+            token::Kind::Variable(name) => bindings.lookup(name),
+            kind @ token::Kind::Number(_) => engine::apply(kind, &arguments),
+            kind => engine::apply(kind, &arguments).map(|value| rounding.step(value)),
+        }
+        .map_err(|e| Diagnostic::at(e.to_string(), &node.value))?;
+        let literal_value = value.to_string();
+        values.push(value);
+
+        if matches!(node.value.kind, token::Kind::Number(_)) {
+            continue;
+        }
+
+        let mut literals = Vec::with_capacity(occurrences.len());
+
+        for occurrence in occurrences {
+            let span = tree.subtree_span(occurrence);
+            let literal = tree.add_node(Token::number(literal_value.clone(), span.line, span.column));
+            tree[literal].origin = Some(span);
+
+            tree.replace_node(occurrence, literal);
+            literals.push(literal);
+        }
+
+        lines.push(unparser::unparse_highlighted(&tree, &literals)?);
+    }
+
+    let result = &values[dag.root];
+    let rounded = rounding.round(result.clone());
+
+    if rounded.to_string() != result.to_string() {
+        let root = tree.get_root().unwrap();
+        let span = tree.subtree_span(root);
+        let literal = tree.add_node(Token::number(rounded.to_string(), span.line, span.column));
+        tree[literal].origin = Some(span);
+
+        tree.replace_node(root, literal);
+        lines.push(unparser::unparse_highlighted(&tree, &[literal])?);
+    }
+
+    Ok(lines)
+}
@@ -1,30 +1,93 @@
-use std::{collections::{VecDeque, HashMap}, rc::Rc, cell::RefCell};
-use crate::{analyzer::syntax_tree::{SyntaxTree, SyntaxNode}, interpreter::engine};
-use rust_decimal::Decimal;
+use crate::{
+    analyzer::{dag::Dag, diagnostic::Diagnostic, syntax_tree::SyntaxTree, token},
+    evaluation::{engine, number::Number, settings::Settings},
+    interpreter::{
+        bindings::Bindings,
+        tracer::{NoTracer, Step, Tracer},
+    },
+};
+use anyhow::Result;
 
+/// Evaluates children left to right before their parent, in reverse polish order. Repeated
+/// subexpressions are merged first and evaluated only once.
+pub fn interprete<N: Number>(tree: SyntaxTree) -> Result<N> {
+    interprete_with(tree, &Bindings::new(), &mut NoTracer)
+}
 
-pub fn interprete(tree: SyntaxTree) -> Rc<Decimal> {
-    let stack = RefCell::new(VecDeque::new());
-    
-    tree.bfs( |node| {
-        stack.borrow_mut().push_back(node);
-    });
+/// Like `interprete`, taking the values of variables from `bindings` and reporting every evaluated
+/// operator and function call to `tracer`.
+pub fn interprete_with<N: Number>(tree: SyntaxTree, bindings: &Bindings<N>, tracer: &mut dyn Tracer<N>) -> Result<N> {
+    interprete_configured(tree, bindings, tracer, &Settings::default())
+}
 
-    let mut enviroment = HashMap::<*const SyntaxNode, Rc<Decimal>>::new();
+/// Like `interprete_with`, failing with `LimitExceeded` if the tree has more nodes or more levels than
+/// `settings.limits` allow or once the evaluation takes more steps or more time than they allow, and rounding
+/// as `settings.rounding` says. Every evaluated operator and function call is a step.
+pub fn interprete_configured<N: Number>(
+    tree: SyntaxTree,
+    bindings: &Bindings<N>,
+    tracer: &mut dyn Tracer<N>,
+    settings: &Settings,
+) -> Result<N> {
+    settings.limits.check_tree(&tree)?;
 
-    while !stack.borrow().is_empty() {
-        let node = stack.borrow_mut().pop_back().unwrap();
-        
-        let mut arguments = Vec::new();
+    let dag = Dag::try_from(&tree)?;
+    let mut budget = settings.limits.budget();
 
-        for child in node.get_children().iter() {
-            arguments.push(Rc::clone(&enviroment.get(&Rc::as_ptr(child)).unwrap()));
+    // values of the evaluated nodes, indexed like `dag.nodes()`
+    let mut enviroment = Vec::<N>::with_capacity(dag.len());
+
+    for node in dag.nodes() {
+        let kind = &node.value.kind;
+        let is_step = !matches!(kind, token::Kind::Number(_) | token::Kind::Variable(_));
+
+        if is_step {
+            budget.step()?;
+        }
+
+        let arguments: Vec<N> = node.children.iter().map(|child| enviroment[*child].clone()).collect();
+        let mut value = match kind {
+            token::Kind::Variable(name) => bindings.lookup(name),
+            kind => engine::apply(kind, &arguments),
+        }
+        .map_err(|e| Diagnostic::at(e.to_string(), &node.value))?;
+
+        if is_step {
+            value = settings.rounding.step(value);
+
+            tracer.step(&Step {
+                span:     node.span,
+                operator: kind,
+                operands: &arguments,
+                result:   value.clone(),
+            })?;
         }
 
-        let value = engine::evaluate(node.value.kind.clone(), arguments);
+        enviroment.push(value);
+    }
+
+    Ok(settings.rounding.round(enviroment.swap_remove(dag.root)))
+}
 
-        enviroment.insert(Rc::as_ptr(&node), value);
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::analyzer::{lexer, parser};
+
+    fn evaluated(source: &str) -> Decimal {
+        interprete(parser::parse(lexer::tokenize(source.chars()).unwrap()).unwrap()).unwrap()
     }
 
-    return Rc::clone(enviroment.get(&Rc::as_ptr(&tree.get_root().unwrap())).unwrap());
-}
\ No newline at end of file
+    #[test]
+    fn operands_are_applied_in_source_order() {
+        assert_eq!(evaluated("5 - 3"), Decimal::from(2));
+        assert_eq!(evaluated("2 ^ 3"), Decimal::from(8));
+        assert_eq!(evaluated("8 / 2"), Decimal::from(4));
+        assert_eq!(evaluated("7 % 4"), Decimal::from(3));
+        assert_eq!(evaluated("10 - 4 - 3"), Decimal::from(3));
+        assert_eq!(evaluated("2 ^ 3 ^ 2"), Decimal::from(512));
+        assert_eq!(evaluated("-(5 - 3)"), Decimal::from(-2));
+    }
+}
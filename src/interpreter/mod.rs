@@ -1,3 +1,9 @@
+#[allow(clippy::module_inception)]
 pub mod interpreter;
-pub use interpreter::interprete;
-pub mod engine;
\ No newline at end of file
+pub use interpreter::{interprete, interprete_configured, interprete_with};
+pub mod batch;
+pub mod bindings;
+pub mod bytecode;
+pub mod closure;
+pub mod explain;
+pub mod tracer;
@@ -0,0 +1,136 @@
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use serde_json::json;
+
+use crate::{
+    analyzer::{
+        export,
+        token::{self, Span},
+    },
+    evaluation::number::Number,
+};
+
+/// Evaluation of a single operator or function call.
+#[derive(Debug, Clone)]
+pub struct Step<'a, N: Number = Decimal> {
+    /// Source range of the evaluated subexpression, operands included.
+    pub span:     Span,
+    pub operator: &'a token::Kind,
+    pub operands: &'a [N],
+    pub result:   N,
+}
+
+/// Receives every step of an evaluation, in evaluation order. Literals are not steps.
+pub trait Tracer<N: Number = Decimal> {
+    fn step(&mut self, step: &Step<N>) -> Result<()>;
+}
+
+/// Ignores every step.
+pub struct NoTracer;
+
+impl<N: Number> Tracer<N> for NoTracer {
+    fn step(&mut self, _step: &Step<N>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes one `Evaluated: 1 + 2 = 3 on line 0 column 1` line per step.
+pub struct TextTracer<W: Write> {
+    output: W,
+}
+
+impl<W: Write> TextTracer<W> {
+    pub fn new(output: W) -> Self {
+        Self { output }
+    }
+}
+
+impl<W: Write, N: Number> Tracer<N> for TextTracer<W> {
+    fn step(&mut self, step: &Step<N>) -> Result<()> {
+        let operands = step.operands;
+
+        let expression = match step.operator {
+            token::Kind::UnaryOperator(op) => format!("{}({})", op, operands[0]),
+            token::Kind::BinaryOperator(op) => format!("{} {} {}", operands[0], op, operands[1]),
+            kind => format!(
+                "{}({})",
+                kind,
+                operands.iter().map(N::to_string).collect::<Vec<_>>().join(", ")
+            ),
+        };
+
+        writeln!(
+            self.output,
+            "Evaluated: {} = {} on line {} column {}",
+            expression, step.result, step.span.line, step.span.column
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Writes every step as a JSON object on its own line, numbers are written as strings to keep their precision:
+/// `{"kind": "binary", "operator": "+", "operands": ["1", "2"], "result": "3", "span": {...}}`.
+pub struct JsonTracer<W: Write> {
+    output: W,
+}
+
+impl<W: Write> JsonTracer<W> {
+    pub fn new(output: W) -> Self {
+        Self { output }
+    }
+}
+
+impl<W: Write, N: Number> Tracer<N> for JsonTracer<W> {
+    fn step(&mut self, step: &Step<N>) -> Result<()> {
+        let event = json!({
+            "kind": export::node_kind(step.operator),
+            "operator": step.operator.to_string(),
+            "operands": step.operands.iter().map(N::to_string).collect::<Vec<_>>(),
+            "result": step.result.to_string(),
+            "span": {
+                "line": step.span.line,
+                "column": step.span.column,
+                "end_line": step.span.end_line,
+                "end_column": step.span.end_column,
+            },
+        });
+
+        writeln!(self.output, "{}", event)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TraceFormat {
+    None,
+    Text,
+    Json,
+}
+
+impl FromStr for TraceFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("unknown trace format \"{}\", expected none, text or json", s)),
+        }
+    }
+}
+
+/// Tracer writing `format` to standard output.
+pub fn stdout_tracer<N: Number>(format: TraceFormat) -> Box<dyn Tracer<N>> {
+    match format {
+        TraceFormat::None => Box::new(NoTracer),
+        TraceFormat::Text => Box::new(TextTracer::new(io::stdout())),
+        TraceFormat::Json => Box::new(JsonTracer::new(io::stdout())),
+    }
+}
@@ -0,0 +1,3 @@
+pub mod analyzer;
+pub mod evaluation;
+pub mod interpreter;
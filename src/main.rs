@@ -1,22 +1,139 @@
-mod analyzer;
-mod interpreter;
-use anyhow::Result;
-use std::fs;
+mod cli;
 
-fn main() -> Result<()> {
-    let expr = fs::read_to_string("examples/expr12.gm").expect("failed to read file");
-    println!("Input Expression:\n\t{}", &expr);
+use anyhow::{anyhow, Result};
+use gematr::{
+    analyzer::{self, semantic_analyzer::Optimized, syntax_tree::SyntaxTree},
+    evaluation::number::{Backend, Number, Promoting},
+    interpreter::{self, bindings::Bindings, tracer::TraceFormat},
+};
+use num_rational::BigRational;
+use std::{
+    env,
+    fs::{self, File},
+    io,
+};
+
+/// Batch mode: writes the expression's value for every row of the CSV file at `path` to standard output,
+/// and nothing else, so that the output stays valid CSV.
+fn evaluate_csv<N: Number>(options: &cli::Options, path: &str, tree: SyntaxTree) -> Result<()> {
+    let tree = optimize::<N>(options, tree)?.tree;
+    let bindings = bindings::<N>(options)?;
+    let output = io::stdout().lock();
+    let (threads, settings) = (options.threads, &options.settings);
+
+    let summary = if path == "-" {
+        interpreter::batch::evaluate_csv(&tree, &bindings, io::stdin().lock(), output, threads, settings)?
+    } else {
+        let input = File::open(path).map_err(|e| anyhow!("failed to read {}: {}", path, e))?;
+        interpreter::batch::evaluate_csv(&tree, &bindings, input, output, threads, settings)?
+    };
+
+    eprintln!("{}", summary);
+    Ok(())
+}
+
+/// Values of the `--set` assignments.
+fn bindings<N: Number>(options: &cli::Options) -> Result<Bindings<N>> {
+    options.assignments.iter().map(|assignment| Bindings::parse_assignment(assignment)).collect()
+}
+
+/// Optimizes the tree as `options` ask, computing constants with the numbers the tree is evaluated with.
+fn optimize<N: Number>(options: &cli::Options, tree: SyntaxTree) -> Result<Optimized> {
+    analyzer::semantic_analyzer::opimize_with::<N>(tree, options.opt_level, &options.rules, &options.settings)
+}
+
+/// Explains, optimizes and evaluates the expression with the numbers of `N`.
+fn evaluate<N: Number>(options: &cli::Options, tree: SyntaxTree) -> Result<()> {
+    let bindings = bindings::<N>(options)?;
 
-    let tokens = analyzer::lexer::tokenize(expr.chars())?;
-    let tree = analyzer::parser::parse(tokens)?;
+    if options.explain {
+        let lines = interpreter::explain::explain(&tree, &bindings, &options.settings)?;
 
-    println!("Syntax Tree:\n{}", tree);
+        println!("Explanation:");
+        for (step, line) in lines.iter().enumerate() {
+            println!("\t{}{}", if step == 0 { "" } else { "= " }, line);
+        }
+        println!();
+    }
 
-    let tree = analyzer::semantic_analyzer::opimize(tree)?;
+    let optimized = optimize::<N>(options, tree)?;
+    let tree = optimized.tree;
 
-    println!("Optimized Syntax Tree:\n{}", tree);
+    if !optimized.passes.is_empty() {
+        println!("Optimization Passes:");
+        for pass in &optimized.passes {
+            println!("\t{}", pass);
+        }
+    }
+
+    if !optimized.rewrites.is_empty() {
+        println!("Rewrites:");
+        for rewrite in &optimized.rewrites {
+            println!("\t{}", rewrite);
+        }
+    }
+
+    println!("Optimized Syntax Tree:\n{}", analyzer::export::export(&tree, options.tree_format)?);
+    println!("Optimized Expression:\n\t{}\n", analyzer::unparser::unparse(&tree)?);
     // https://cs.lmu.edu/~ray/notes/compilerarchitecture/
 
-    println!("Result: {}", interpreter::interprete(tree));
+    let trace = if options.explain { TraceFormat::None } else { options.trace };
+    let mut tracer = interpreter::tracer::stdout_tracer(trace);
+    let result = interpreter::interprete_configured(tree, &bindings, tracer.as_mut(), &options.settings)?;
+    println!("Result: {}", result);
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let options = cli::Options::parse(env::args().skip(1))?;
+
+    let source = fs::read_to_string(&options.path).expect("failed to read file");
+
+    let (tree, tokens) = match options.input_format {
+        cli::InputFormat::Expr => {
+            let tokens = analyzer::lexer::tokenize(source.chars())?;
+            (analyzer::parser::parse_limited(&tokens, &options.settings.limits)?, tokens)
+        }
+
+        cli::InputFormat::Json => {
+            let tree = analyzer::import::from_json(&source)?;
+            options.settings.limits.check_tree(&tree)?;
+
+            (tree, vec![])
+        }
+    };
+
+    let warnings = analyzer::lint::lint(&tokens, &tree)?;
+
+    if let Some(path) = &options.csv {
+        for warning in &warnings {
+            eprintln!("Warning: {}", warning);
+        }
+
+        return match options.number {
+            Backend::Decimal => evaluate_csv::<Promoting>(&options, path, tree),
+            Backend::Float => evaluate_csv::<f64>(&options, path, tree),
+            Backend::Rational => evaluate_csv::<BigRational>(&options, path, tree),
+        };
+    }
+
+    match options.input_format {
+        cli::InputFormat::Expr => println!("Input Expression:\n\t{}", &source),
+        cli::InputFormat::Json => println!("Input Expression:\n\t{}", analyzer::unparser::unparse(&tree)?),
+    }
+
+    if !warnings.is_empty() {
+        println!("Warnings:");
+        for warning in &warnings {
+            println!("\t{}", warning);
+        }
+    }
+
+    println!("Syntax Tree:\n{}", analyzer::export::export(&tree, options.tree_format)?);
+
+    match options.number {
+        Backend::Decimal => evaluate::<Promoting>(&options, tree),
+        Backend::Float => evaluate::<f64>(&options, tree),
+        Backend::Rational => evaluate::<BigRational>(&options, tree),
+    }
+}
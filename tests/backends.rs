@@ -0,0 +1,117 @@
+//! Every back end has to evaluate an expression to the same value as the interpreter, or fail with the
+//! same error. The one exception is a program the bytecode compiler rejects, see `bytecode::compile`.
+
+use anyhow::Result;
+use gematr::{
+    analyzer::{lexer, parser},
+    evaluation::settings::{Precision, RoundAt, Rounding, RoundingMode, Settings},
+    interpreter::{self, bindings::Bindings, bytecode, closure, tracer::NoTracer},
+};
+use rust_decimal::Decimal;
+
+const CORPUS: [&str; 15] = [
+    "1 + 4 + 86 - 439 + 57^0.5 + min(5^40, -0.00000004859) - 0.38^-64",
+    "x * 2 + 1",
+    "-(x - y) * +y % 3",
+    "max(x, y, 1.50) + min(x, 7.0)",
+    "(x + 1) * (x + 1) / (y - y + 3)",
+    "x ^ 0.5 + 2 ^ -y",
+    "x / (y + 2)",
+    "x % (y + 2)",
+    "10 ^ (x * 100)",
+    "max(z, 1 / 0)",
+    "foo(x / 0)",
+    "foo(x)",
+    "min()",
+    "1 / 3 * 3 + +(x / 3) - -(y / 7)",
+    "max(x / 3, y / 3) * 3",
+];
+
+fn show(result: &Result<Decimal>) -> String {
+    match result {
+        Ok(value) => value.to_string(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+/// Evaluates the corpus on every back end with `settings` and compares the results with the interpreter's.
+fn check(settings: &Settings) {
+    let bindings: Bindings = [("x", 7), ("y", -2)]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.into()))
+        .collect();
+
+    for expression in CORPUS {
+        let tree = parser::parse(lexer::tokenize(expression.chars()).unwrap()).unwrap();
+
+        let expected = interpreter::interprete_configured(tree.clone(), &bindings, &mut NoTracer, settings);
+        let closure = closure::compile_with(&tree, settings)(&bindings);
+
+        assert_eq!(show(&closure), show(&expected), "closure evaluates `{}` differently", expression);
+
+        match bytecode::compile(&tree) {
+            Ok(program) => {
+                let vm = bytecode::Vm::with_settings(settings).run(&program, &bindings);
+                assert_eq!(show(&vm), show(&expected), "bytecode evaluates `{}` differently", expression);
+            }
+            Err(e) => assert!(expected.is_err(), "bytecode rejects `{}` that evaluates: {}", expression, e),
+        }
+    }
+}
+
+#[test]
+fn back_ends_agree_with_the_interpreter() {
+    check(&Settings::default());
+}
+
+#[test]
+fn back_ends_round_every_step_like_the_interpreter() {
+    let rounding = Rounding {
+        precision: Precision::Places(2),
+        mode:      RoundingMode::Floor,
+        at:        RoundAt::EveryStep,
+    };
+
+    check(&Settings {
+        rounding,
+        ..Settings::default()
+    });
+}
+
+#[test]
+fn back_ends_count_steps_like_the_interpreter() {
+    let bindings: Bindings = [("x".to_string(), Decimal::from(7))].into_iter().collect();
+    let tree = parser::parse(lexer::tokenize("-x * 2 + max(x, 1)".chars()).unwrap()).unwrap();
+
+    for (max_steps, expected) in [(4, "-7"), (3, "error: evaluation takes more than 3 steps")] {
+        let mut settings = Settings::default();
+        settings.limits.max_steps = Some(max_steps);
+
+        let interpreted = interpreter::interprete_configured(tree.clone(), &bindings, &mut NoTracer, &settings);
+        let closure = closure::compile_with(&tree, &settings)(&bindings);
+        let program = bytecode::compile(&tree).unwrap();
+        let vm = bytecode::Vm::with_settings(&settings).run(&program, &bindings);
+        let explained = interpreter::explain::explain(&tree, &bindings, &settings).map(|lines| lines.len().into());
+
+        assert_eq!(show(&interpreted), expected);
+        assert_eq!(show(&closure), expected);
+        assert_eq!(show(&vm), expected);
+        assert_eq!(show(&explained).starts_with("error"), expected.starts_with("error"));
+    }
+}
+
+#[test]
+fn the_interpreter_checks_the_size_of_the_tree() {
+    let tree = parser::parse(lexer::tokenize("(1 + 2) * 3".chars()).unwrap()).unwrap();
+
+    let mut settings = Settings::default();
+    settings.limits.max_depth = Some(2);
+    let deep = interpreter::interprete_configured::<Decimal>(tree.clone(), &Bindings::new(), &mut NoTracer, &settings);
+
+    let mut settings = Settings::default();
+    settings.limits.max_nodes = Some(4);
+    let large = interpreter::interprete_configured::<Decimal>(tree, &Bindings::new(), &mut NoTracer, &settings);
+
+    assert_eq!(show(&deep), "error: expression is nested more than 2 levels deep");
+    assert_eq!(show(&large), "error: expression has more than 4 nodes");
+}